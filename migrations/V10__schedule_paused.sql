@@ -0,0 +1 @@
+ALTER TABLE schedule ADD COLUMN paused boolean NOT NULL DEFAULT false;
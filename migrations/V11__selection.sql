@@ -0,0 +1,3 @@
+ALTER TABLE autoresponse_phrases ADD COLUMN selection character varying(20) NOT NULL DEFAULT 'random';
+
+ALTER TABLE schedule ADD COLUMN selection character varying(20) NOT NULL DEFAULT 'random';
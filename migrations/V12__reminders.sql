@@ -0,0 +1,13 @@
+CREATE TABLE reminders (
+    id serial PRIMARY KEY,
+    chat_id bigint NOT NULL,
+    user_id bigint NOT NULL,
+    message_id bigint NOT NULL,
+    text text NOT NULL,
+    remind_at timestamp with time zone NOT NULL,
+    created_at timestamp with time zone NOT NULL DEFAULT now()
+);
+
+CREATE INDEX reminders_remind_at_idx ON reminders (remind_at);
+
+CREATE INDEX reminders_chat_user_idx ON reminders (chat_id, user_id);
@@ -0,0 +1,3 @@
+ALTER TABLE autoresponse_phrases ADD COLUMN priority integer NOT NULL DEFAULT 0;
+
+ALTER TABLE autoresponse_phrases ADD COLUMN stop boolean NOT NULL DEFAULT true;
@@ -0,0 +1,5 @@
+ALTER TABLE autoresponse_phrases ADD COLUMN ignore_case boolean NOT NULL DEFAULT false;
+
+ALTER TABLE autoresponse_phrases ADD COLUMN whole_word boolean NOT NULL DEFAULT false;
+
+ALTER TABLE autoresponse_phrases ADD COLUMN normalize boolean NOT NULL DEFAULT false;
@@ -0,0 +1,6 @@
+ALTER TABLE autoresponse_phrases ADD COLUMN cooldown integer NOT NULL DEFAULT 0 CHECK (cooldown >= 0);
+
+ALTER TABLE autoresponse_phrases ADD COLUMN user_cooldown integer NOT NULL DEFAULT 0 CHECK (user_cooldown >= 0);
+
+ALTER TABLE autoresponse_phrases ADD COLUMN probability double precision NOT NULL DEFAULT 1
+    CHECK (probability >= 0 AND probability <= 1);
@@ -0,0 +1,3 @@
+ALTER TABLE autoresponse_phrases ADD COLUMN hits bigint NOT NULL DEFAULT 0;
+
+ALTER TABLE autoresponse_phrases ADD COLUMN last_hit_at timestamptz;
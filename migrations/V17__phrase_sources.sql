@@ -0,0 +1,5 @@
+ALTER TABLE autoresponse_phrases ADD COLUMN sources text[] NOT NULL DEFAULT '{text,caption}';
+
+ALTER TABLE autoresponse_phrases ADD COLUMN include_forwarded boolean NOT NULL DEFAULT true;
+
+ALTER TABLE autoresponse_phrases ADD COLUMN include_bots boolean NOT NULL DEFAULT true;
@@ -0,0 +1,8 @@
+-- Outputs written before media support are texts, so those looking like media get `text:` prefix
+UPDATE autoresponse_phrases
+SET output = ARRAY(
+    SELECT CASE WHEN x ~ '^(sticker|animation|photo|text):' THEN 'text:' || x ELSE x END
+    FROM unnest(output) WITH ORDINALITY AS t(x, n)
+    ORDER BY n
+)
+WHERE EXISTS (SELECT 1 FROM unnest(output) AS x WHERE x ~ '^(sticker|animation|photo|text):');
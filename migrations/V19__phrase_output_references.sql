@@ -0,0 +1,8 @@
+-- Outputs of `matches` phrases written before references are literal texts, so `$` is escaped there
+UPDATE autoresponse_phrases
+SET output = ARRAY(
+    SELECT replace(x, '$', '$$')
+    FROM unnest(output) WITH ORDINALITY AS t(x, n)
+    ORDER BY n
+)
+WHERE rule_type = 'matches' AND EXISTS (SELECT 1 FROM unnest(output) AS x WHERE x LIKE '%$%');
@@ -0,0 +1,7 @@
+ALTER TABLE autoresponse_phrases ADD COLUMN chat_id bigint;
+
+ALTER TABLE feeds ADD COLUMN chat_id bigint;
+
+ALTER TABLE greetings ADD COLUMN chat_id bigint;
+
+ALTER TABLE schedule ADD COLUMN chat_id bigint;
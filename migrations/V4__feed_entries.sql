@@ -0,0 +1,10 @@
+CREATE TABLE feed_entries (
+    feed_id integer NOT NULL REFERENCES feeds (id) ON DELETE CASCADE,
+    entry_id text NOT NULL,
+    created_at timestamp without time zone NOT NULL DEFAULT now(),
+    PRIMARY KEY (feed_id, entry_id)
+);
+
+ALTER TABLE feeds ADD COLUMN initialized boolean NOT NULL DEFAULT false;
+
+ALTER TABLE feeds DROP COLUMN last_entry;
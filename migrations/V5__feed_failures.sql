@@ -0,0 +1,7 @@
+ALTER TABLE feeds ADD COLUMN enabled boolean NOT NULL DEFAULT true;
+
+ALTER TABLE feeds ADD COLUMN failures integer NOT NULL DEFAULT 0;
+
+ALTER TABLE feeds ADD COLUMN last_error text;
+
+ALTER TABLE feeds ADD COLUMN retry_at timestamp without time zone;
@@ -0,0 +1,3 @@
+ALTER TABLE feeds ADD COLUMN etag text;
+
+ALTER TABLE feeds ADD COLUMN last_modified text;
@@ -0,0 +1,5 @@
+ALTER TABLE schedule ADD COLUMN cron character varying(255);
+
+ALTER TABLE schedule ALTER COLUMN day DROP NOT NULL;
+
+ALTER TABLE schedule ALTER COLUMN "time" DROP NOT NULL;
@@ -0,0 +1 @@
+ALTER TABLE schedule ADD COLUMN timezone character varying(255);
@@ -0,0 +1,3 @@
+ALTER TABLE schedule ADD COLUMN last_fired_at timestamp with time zone;
+
+ALTER TABLE schedule ADD COLUMN missed_runs character varying(20) NOT NULL DEFAULT 'skip';
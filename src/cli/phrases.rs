@@ -0,0 +1,631 @@
+use crate::{
+    handler::autoresponse::{
+        get_stats, AutoresponseError, Input, Output, PhraseStats, RuleType, Rules, Source, TextForm, Verdict,
+    },
+    selection::Selection,
+};
+use carapax::types::Integer;
+use serde::{Deserialize, Serialize};
+use serde_json::Error as JsonError;
+use std::{error::Error, fmt, fs, io::Error as IoError};
+use tokio_postgres::{types::ToSql, Client as PgClient, Error as PostgresError};
+
+/// Default number of phrases in each list of `phrases stats`
+const STATS_LIMIT: i64 = 20;
+
+const USAGE: &str = "Usage:
+    phrases list
+    phrases add [--reply-to] [--shuffle] [--continue] [--priority <n>] [--ignore-case] [--whole-word] [--normalize]
+        [--cooldown <seconds>] [--user-cooldown <seconds>] [--probability <p>]
+        [--sources <source,...>] [--no-forwarded] [--no-bots] [--chat <id>] <contains|equals|matches> <input> <output>...
+    phrases remove <id>
+    phrases stats [limit]
+    phrases test [--chat <id>] [--source <source>] [--forwarded] [--bot] <text>
+    phrases import <file>
+    phrases export [file]";
+
+/// Query used by `phrases add` and `phrases import`, see `Phrase::params()`
+const INSERT_PHRASE: &str =
+    "INSERT INTO autoresponse_phrases (input, rule_type, reply_to, output, chat_id, selection, priority, stop,
+        ignore_case, whole_word, normalize, cooldown, user_cooldown, probability,
+        sources, include_forwarded, include_bots)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+    RETURNING id";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Phrase {
+    input: String,
+    rule_type: String,
+    #[serde(default)]
+    reply_to: bool,
+    output: Vec<String>,
+    #[serde(default)]
+    chat_id: Option<Integer>,
+    #[serde(default = "default_selection")]
+    selection: String,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default = "default_stop")]
+    stop: bool,
+    #[serde(default)]
+    ignore_case: bool,
+    #[serde(default)]
+    whole_word: bool,
+    #[serde(default)]
+    normalize: bool,
+    #[serde(default)]
+    cooldown: i32,
+    #[serde(default)]
+    user_cooldown: i32,
+    #[serde(default = "default_probability")]
+    probability: f64,
+    #[serde(default = "default_sources")]
+    sources: Vec<String>,
+    #[serde(default = "default_include")]
+    include_forwarded: bool,
+    #[serde(default = "default_include")]
+    include_bots: bool,
+}
+
+fn default_selection() -> String {
+    String::from("random")
+}
+
+fn default_stop() -> bool {
+    true
+}
+
+fn default_probability() -> f64 {
+    1.0
+}
+
+fn default_sources() -> Vec<String> {
+    vec![String::from("text"), String::from("caption")]
+}
+
+fn default_include() -> bool {
+    true
+}
+
+impl Phrase {
+    /// Parameters of `INSERT_PHRASE` query
+    fn params(&self) -> [&(dyn ToSql + Sync); 17] {
+        [
+            &self.input,
+            &self.rule_type,
+            &self.reply_to,
+            &self.output,
+            &self.chat_id,
+            &self.selection,
+            &self.priority,
+            &self.stop,
+            &self.ignore_case,
+            &self.whole_word,
+            &self.normalize,
+            &self.cooldown,
+            &self.user_cooldown,
+            &self.probability,
+            &self.sources,
+            &self.include_forwarded,
+            &self.include_bots,
+        ]
+    }
+
+    fn validate(&self) -> Result<(), PhrasesError> {
+        let rule_type = self.rule_type.parse::<RuleType>()?;
+        let form = TextForm {
+            ignore_case: self.ignore_case,
+            normalize: self.normalize,
+        };
+        // Normalization may leave nothing of a plain input, such rule would match any text
+        if self.input.is_empty() || (rule_type != RuleType::Matches && form.apply(&self.input).is_empty()) {
+            return Err(PhrasesError::EmptyInput);
+        }
+        if self.output.is_empty() {
+            return Err(PhrasesError::EmptyOutput);
+        }
+        for output in &self.output {
+            if let Output::Text(text) = Output::parse(output)? {
+                if text.is_empty() {
+                    return Err(PhrasesError::EmptyOutput);
+                }
+            }
+        }
+        if self.cooldown < 0 || self.user_cooldown < 0 {
+            return Err(PhrasesError::NegativeCooldown);
+        }
+        if !(0.0..=1.0).contains(&self.probability) {
+            return Err(PhrasesError::InvalidProbability);
+        }
+        if self.sources.is_empty() {
+            return Err(PhrasesError::EmptySources);
+        }
+        for source in &self.sources {
+            source.parse::<Source>()?;
+        }
+        rule_type.validate(&self.input)?;
+        self.selection
+            .parse::<Selection>()
+            .map_err(AutoresponseError::UnknownSelection)?;
+        Ok(())
+    }
+}
+
+/// Runs `phrases` subcommand
+///
+/// # Arguments
+///
+/// * pg_client - PostgreSQL client
+/// * args - Arguments following `phrases`
+pub async fn run(pg_client: &mut PgClient, args: Vec<String>) -> Result<(), PhrasesError> {
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some("list") => list(pg_client).await,
+        Some("add") => add(pg_client, args.collect()).await,
+        Some("remove") => match args.next().and_then(|x| x.parse::<i32>().ok()) {
+            Some(id) => remove(pg_client, id).await,
+            None => Err(PhrasesError::Usage),
+        },
+        Some("stats") => match args.next().map(|x| x.parse::<i64>()) {
+            Some(Ok(limit)) if limit > 0 => stats(pg_client, limit).await,
+            Some(_) => Err(PhrasesError::Usage),
+            None => stats(pg_client, STATS_LIMIT).await,
+        },
+        Some("test") => test(pg_client, args.collect()).await,
+        Some("import") => match args.next() {
+            Some(path) => import(pg_client, &path).await,
+            None => Err(PhrasesError::Usage),
+        },
+        Some("export") => export(pg_client, args.next()).await,
+        _ => Err(PhrasesError::Usage),
+    }
+}
+
+async fn list(pg_client: &PgClient) -> Result<(), PhrasesError> {
+    for row in pg_client
+        .query(
+            "SELECT id, input, rule_type, reply_to, output, chat_id, selection, priority, stop,
+                ignore_case, whole_word, normalize, cooldown, user_cooldown, probability,
+                sources, include_forwarded, include_bots
+            FROM autoresponse_phrases
+            ORDER BY id",
+            &[],
+        )
+        .await
+        .map_err(PhrasesError::GetPhrases)?
+    {
+        let id: i32 = row.get(0);
+        let input: String = row.get(1);
+        let rule_type: String = row.get(2);
+        let reply_to: bool = row.get(3);
+        let output: Vec<String> = row.get(4);
+        let chat_id: Option<Integer> = row.get(5);
+        let selection: String = row.get(6);
+        let priority: i32 = row.get(7);
+        let stop: bool = row.get(8);
+        let ignore_case: bool = row.get(9);
+        let whole_word: bool = row.get(10);
+        let normalize: bool = row.get(11);
+        let cooldown: i32 = row.get(12);
+        let user_cooldown: i32 = row.get(13);
+        let probability: f64 = row.get(14);
+        let sources: Vec<String> = row.get(15);
+        let include_forwarded: bool = row.get(16);
+        let include_bots: bool = row.get(17);
+        println!(
+            "{}\t{}\t{:?}\treply_to={}\toutputs={}\tselection={}\tpriority={}\tstop={}\t\
+             ignore_case={}\twhole_word={}\tnormalize={}\tcooldown={}\tuser_cooldown={}\tprobability={}\t\
+             sources={}\tinclude_forwarded={}\tinclude_bots={}\tchat={}",
+            id,
+            rule_type,
+            input,
+            reply_to,
+            output.len(),
+            selection,
+            priority,
+            stop,
+            ignore_case,
+            whole_word,
+            normalize,
+            cooldown,
+            user_cooldown,
+            probability,
+            sources.join(","),
+            include_forwarded,
+            include_bots,
+            chat_id.map(|x| x.to_string()).unwrap_or_else(|| String::from("*"))
+        );
+    }
+    Ok(())
+}
+
+async fn add(pg_client: &PgClient, args: Vec<String>) -> Result<(), PhrasesError> {
+    let mut reply_to = false;
+    let mut selection = default_selection();
+    let mut priority = 0;
+    let mut stop = default_stop();
+    let mut ignore_case = false;
+    let mut whole_word = false;
+    let mut normalize = false;
+    let mut cooldown = 0;
+    let mut user_cooldown = 0;
+    let mut probability = default_probability();
+    let mut sources = default_sources();
+    let mut include_forwarded = default_include();
+    let mut include_bots = default_include();
+    let mut chat_id = None;
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--reply-to" => reply_to = true,
+            "--shuffle" => selection = String::from("shuffle"),
+            "--continue" => stop = false,
+            "--ignore-case" => ignore_case = true,
+            "--whole-word" => whole_word = true,
+            "--normalize" => normalize = true,
+            "--cooldown" => match args.next().and_then(|x| x.parse::<i32>().ok()) {
+                Some(value) => cooldown = value,
+                None => return Err(PhrasesError::Usage),
+            },
+            "--user-cooldown" => match args.next().and_then(|x| x.parse::<i32>().ok()) {
+                Some(value) => user_cooldown = value,
+                None => return Err(PhrasesError::Usage),
+            },
+            "--probability" => match args.next().and_then(|x| x.parse::<f64>().ok()) {
+                Some(value) => probability = value,
+                None => return Err(PhrasesError::Usage),
+            },
+            "--sources" => match args.next() {
+                Some(value) => sources = value.split(',').map(String::from).collect(),
+                None => return Err(PhrasesError::Usage),
+            },
+            "--no-forwarded" => include_forwarded = false,
+            "--no-bots" => include_bots = false,
+            "--priority" => match args.next().and_then(|x| x.parse::<i32>().ok()) {
+                Some(value) => priority = value,
+                None => return Err(PhrasesError::Usage),
+            },
+            "--chat" => match args.next().and_then(|x| x.parse::<Integer>().ok()) {
+                Some(value) => chat_id = Some(value),
+                None => return Err(PhrasesError::Usage),
+            },
+            _ => positional.push(arg),
+        }
+    }
+    let mut args = positional.into_iter();
+    let (rule_type, input) = match (args.next(), args.next()) {
+        (Some(rule_type), Some(input)) => (rule_type, input),
+        _ => return Err(PhrasesError::Usage),
+    };
+    let phrase = Phrase {
+        input,
+        rule_type,
+        reply_to,
+        output: args.collect(),
+        chat_id,
+        selection,
+        priority,
+        stop,
+        ignore_case,
+        whole_word,
+        normalize,
+        cooldown,
+        user_cooldown,
+        probability,
+        sources,
+        include_forwarded,
+        include_bots,
+    };
+    phrase.validate()?;
+    let row = pg_client
+        .query_one(INSERT_PHRASE, &phrase.params())
+        .await
+        .map_err(PhrasesError::SavePhrase)?;
+    let id: i32 = row.get(0);
+    println!("Phrase added: {}", id);
+    Ok(())
+}
+
+async fn remove(pg_client: &PgClient, id: i32) -> Result<(), PhrasesError> {
+    let count = pg_client
+        .execute("DELETE FROM autoresponse_phrases WHERE id = $1", &[&id])
+        .await
+        .map_err(PhrasesError::RemovePhrase)?;
+    if count == 0 {
+        return Err(PhrasesError::NotFound(id));
+    }
+    println!("Phrase removed: {}", id);
+    Ok(())
+}
+
+async fn stats(pg_client: &PgClient, limit: i64) -> Result<(), PhrasesError> {
+    let (most, least) = get_stats(pg_client, limit).await?;
+    println!("Most used:");
+    print_stats(&most);
+    println!("\nLeast used:");
+    print_stats(&least);
+    Ok(())
+}
+
+fn print_stats(items: &[PhraseStats]) {
+    for item in items {
+        println!(
+            "{}\t{}\t{:?}\thits={}\tlast_hit={}",
+            item.id,
+            item.rule_type,
+            item.input,
+            item.hits,
+            item.last_hit_at
+                .map(|x| x.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_else(|| String::from("never"))
+        );
+    }
+}
+
+async fn test(pg_client: &PgClient, args: Vec<String>) -> Result<(), PhrasesError> {
+    let mut chat_id = 0;
+    let mut source = Source::Text;
+    let mut is_forwarded = false;
+    let mut is_from_bot = false;
+    let mut text = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--chat" => match args.next().and_then(|x| x.parse::<Integer>().ok()) {
+                Some(value) => chat_id = value,
+                None => return Err(PhrasesError::Usage),
+            },
+            "--source" => match args.next() {
+                Some(value) => source = value.parse()?,
+                None => return Err(PhrasesError::Usage),
+            },
+            "--forwarded" => is_forwarded = true,
+            "--bot" => is_from_bot = true,
+            _ if text.is_none() => text = Some(arg),
+            _ => return Err(PhrasesError::Usage),
+        }
+    }
+    let text = text.ok_or(PhrasesError::Usage)?;
+    let rules = Rules::load(pg_client).await?;
+    let input = Input {
+        chat_id,
+        parts: vec![(source, &text)],
+        is_forwarded,
+        is_from_bot,
+    };
+    let explanations = rules.explain(&input);
+    if explanations.is_empty() {
+        println!("No phrases match");
+    }
+    for explanation in &explanations {
+        println!("{}", explanation);
+    }
+    let replies: Vec<String> = explanations
+        .iter()
+        .filter(|x| x.verdict == Verdict::Replies)
+        .map(|x| format!("#{}", x.id))
+        .collect();
+    if !replies.is_empty() {
+        println!(
+            "Replies: {} (cooldowns and probability are not checked)",
+            replies.join(", ")
+        );
+    }
+    Ok(())
+}
+
+async fn import(pg_client: &mut PgClient, path: &str) -> Result<(), PhrasesError> {
+    let data = fs::read_to_string(path).map_err(PhrasesError::ReadFile)?;
+    let phrases: Vec<Phrase> = serde_json::from_str(&data)?;
+    for (idx, phrase) in phrases.iter().enumerate() {
+        if let Err(err) = phrase.validate() {
+            return Err(PhrasesError::BadPhrase(idx, Box::new(err)));
+        }
+    }
+    let transaction = pg_client.transaction().await.map_err(PhrasesError::SavePhrase)?;
+    for phrase in &phrases {
+        transaction
+            .execute(INSERT_PHRASE, &phrase.params())
+            .await
+            .map_err(PhrasesError::SavePhrase)?;
+    }
+    transaction.commit().await.map_err(PhrasesError::SavePhrase)?;
+    println!("Phrases imported: {}", phrases.len());
+    Ok(())
+}
+
+async fn export(pg_client: &PgClient, path: Option<String>) -> Result<(), PhrasesError> {
+    let phrases: Vec<Phrase> = pg_client
+        .query(
+            "SELECT input, rule_type, reply_to, output, chat_id, selection, priority, stop,
+                ignore_case, whole_word, normalize, cooldown, user_cooldown, probability,
+                sources, include_forwarded, include_bots
+            FROM autoresponse_phrases
+            ORDER BY id",
+            &[],
+        )
+        .await
+        .map_err(PhrasesError::GetPhrases)?
+        .into_iter()
+        .map(|row| Phrase {
+            input: row.get(0),
+            rule_type: row.get(1),
+            reply_to: row.get(2),
+            output: row.get(3),
+            chat_id: row.get(4),
+            selection: row.get(5),
+            priority: row.get(6),
+            stop: row.get(7),
+            ignore_case: row.get(8),
+            whole_word: row.get(9),
+            normalize: row.get(10),
+            cooldown: row.get(11),
+            user_cooldown: row.get(12),
+            probability: row.get(13),
+            sources: row.get(14),
+            include_forwarded: row.get(15),
+            include_bots: row.get(16),
+        })
+        .collect();
+    let data = serde_json::to_string_pretty(&phrases)?;
+    match path {
+        Some(path) => fs::write(path, data).map_err(PhrasesError::WriteFile)?,
+        None => println!("{}", data),
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum PhrasesError {
+    Autoresponse(AutoresponseError),
+    BadPhrase(usize, Box<PhrasesError>),
+    EmptyInput,
+    EmptyOutput,
+    EmptySources,
+    GetPhrases(PostgresError),
+    InvalidProbability,
+    Json(JsonError),
+    NegativeCooldown,
+    NotFound(i32),
+    ReadFile(IoError),
+    RemovePhrase(PostgresError),
+    SavePhrase(PostgresError),
+    Usage,
+    WriteFile(IoError),
+}
+
+impl From<AutoresponseError> for PhrasesError {
+    fn from(err: AutoresponseError) -> Self {
+        PhrasesError::Autoresponse(err)
+    }
+}
+
+impl From<JsonError> for PhrasesError {
+    fn from(err: JsonError) -> Self {
+        PhrasesError::Json(err)
+    }
+}
+
+impl Error for PhrasesError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PhrasesError::Autoresponse(err) => Some(err),
+            PhrasesError::BadPhrase(_, err) => Some(err),
+            PhrasesError::GetPhrases(err) => Some(err),
+            PhrasesError::Json(err) => Some(err),
+            PhrasesError::ReadFile(err) => Some(err),
+            PhrasesError::RemovePhrase(err) => Some(err),
+            PhrasesError::SavePhrase(err) => Some(err),
+            PhrasesError::WriteFile(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PhrasesError {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PhrasesError::Autoresponse(err) => write!(out, "{}", err),
+            PhrasesError::BadPhrase(idx, err) => write!(out, "bad phrase at index {}: {}", idx, err),
+            PhrasesError::EmptyInput => write!(out, "input can not be empty"),
+            PhrasesError::EmptyOutput => write!(out, "output can not be empty"),
+            PhrasesError::EmptySources => write!(out, "sources can not be empty"),
+            PhrasesError::GetPhrases(err) => write!(out, "failed to get phrases: {}", err),
+            PhrasesError::InvalidProbability => write!(out, "probability must be between 0 and 1"),
+            PhrasesError::Json(err) => write!(out, "failed to process JSON: {}", err),
+            PhrasesError::NegativeCooldown => write!(out, "cooldown can not be negative"),
+            PhrasesError::NotFound(id) => write!(out, "phrase not found: {}", id),
+            PhrasesError::ReadFile(err) => write!(out, "failed to read file: {}", err),
+            PhrasesError::RemovePhrase(err) => write!(out, "failed to remove phrase: {}", err),
+            PhrasesError::SavePhrase(err) => write!(out, "failed to save phrase: {}", err),
+            PhrasesError::Usage => write!(out, "{}", USAGE),
+            PhrasesError::WriteFile(err) => write!(out, "failed to write file: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phrase(rule_type: &str, input: &str, output: &[&str]) -> Phrase {
+        Phrase {
+            input: String::from(input),
+            rule_type: String::from(rule_type),
+            reply_to: false,
+            output: output.iter().map(|x| String::from(*x)).collect(),
+            chat_id: None,
+            selection: default_selection(),
+            priority: 0,
+            stop: default_stop(),
+            ignore_case: false,
+            whole_word: false,
+            normalize: false,
+            cooldown: 0,
+            user_cooldown: 0,
+            probability: default_probability(),
+            sources: default_sources(),
+            include_forwarded: default_include(),
+            include_bots: default_include(),
+        }
+    }
+
+    #[test]
+    fn validate() {
+        assert!(phrase("contains", "rust", &["jerk"]).validate().is_ok());
+        assert!(phrase("equals", "rust", &["jerk"]).validate().is_ok());
+        assert!(phrase("matches", "^ru+st$", &["jerk"]).validate().is_ok());
+
+        let err = phrase("startswith", "rust", &["jerk"]).validate().unwrap_err();
+        assert_eq!(err.to_string(), "unknown rule type: startswith");
+
+        let err = phrase("matches", "(rust", &["jerk"]).validate().unwrap_err();
+        assert!(err.to_string().starts_with("failed to parse regex"));
+
+        let err = phrase("contains", "", &["jerk"]).validate().unwrap_err();
+        assert_eq!(err.to_string(), "input can not be empty");
+
+        let err = phrase("contains", "rust", &[]).validate().unwrap_err();
+        assert_eq!(err.to_string(), "output can not be empty");
+
+        let mut punctuation = phrase("contains", "?!", &["jerk"]);
+        assert!(punctuation.validate().is_ok());
+        punctuation.normalize = true;
+        let err = punctuation.validate().unwrap_err();
+        assert_eq!(err.to_string(), "input can not be empty");
+
+        let err = phrase("contains", "rust", &["text:"]).validate().unwrap_err();
+        assert_eq!(err.to_string(), "output can not be empty");
+
+        assert!(phrase("contains", "rust", &["jerk", "sticker:CAAD"]).validate().is_ok());
+        let err = phrase("contains", "rust", &["photo:"]).validate().unwrap_err();
+        assert_eq!(err.to_string(), "file ID of media output can not be empty");
+
+        let mut limited = phrase("contains", "rust", &["jerk"]);
+        limited.user_cooldown = -1;
+        let err = limited.validate().unwrap_err();
+        assert_eq!(err.to_string(), "cooldown can not be negative");
+        limited.user_cooldown = 60;
+        limited.probability = 1.5;
+        let err = limited.validate().unwrap_err();
+        assert_eq!(err.to_string(), "probability must be between 0 and 1");
+        limited.probability = 0.5;
+        assert!(limited.validate().is_ok());
+
+        let mut stickers = phrase("equals", "🦀", &["jerk"]);
+        stickers.sources = vec![String::from("sticker_emoji"), String::from("sticker_set")];
+        assert!(stickers.validate().is_ok());
+        stickers.sources = vec![String::from("voice")];
+        let err = stickers.validate().unwrap_err();
+        assert_eq!(err.to_string(), "unknown source: voice");
+        stickers.sources.clear();
+        let err = stickers.validate().unwrap_err();
+        assert_eq!(err.to_string(), "sources can not be empty");
+
+        let mut unknown = phrase("contains", "rust", &["jerk"]);
+        unknown.selection = String::from("cycle");
+        let err = unknown.validate().unwrap_err();
+        assert_eq!(err.to_string(), "unknown selection: cycle");
+    }
+}
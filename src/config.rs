@@ -1,4 +1,5 @@
 use carapax::{types::Integer, Config as ApiConfig, ParseProxyError};
+use chrono_tz::{Europe, Tz};
 use envy::Error as EnvyError;
 use serde::Deserialize;
 use std::{
@@ -16,7 +17,9 @@ struct RawConfig {
     webhook_path: String,
     postgres_url: String,
     redis_url: String,
-    chat_id: Integer,
+    chat_id: Vec<Integer>,
+    timezone: Option<String>,
+    greeting_policy: Option<String>,
 }
 
 fn default_webhook_path() -> String {
@@ -30,12 +33,19 @@ pub struct Config {
     pub webhook_url: Option<(SocketAddr, String)>,
     pub redis_url: String,
     pub postgres_url: String,
-    pub chat_id: Integer,
+    pub chat_ids: Vec<Integer>,
+    /// Default timezone of scheduled messages
+    pub timezone: Tz,
+    /// How members who joined at once are greeted
+    pub greeting_policy: GreetingPolicy,
 }
 
 impl Config {
     pub fn from_env() -> Result<Self, ConfigError> {
         let raw: RawConfig = envy::prefixed("RUSTJERKBOT_").from_env()?;
+        if raw.chat_id.is_empty() {
+            return Err(ConfigError::NoChats);
+        }
         let webhook_url = match raw.webhook_address {
             Some(addr) => Some((
                 addr.parse::<SocketAddr>().map_err(ConfigError::WebhookAddress)?,
@@ -43,13 +53,24 @@ impl Config {
             )),
             None => None,
         };
+        let timezone = match raw.timezone {
+            Some(name) => name.parse().map_err(|_| ConfigError::Timezone(name))?,
+            None => Europe::Moscow,
+        };
+        let greeting_policy = match raw.greeting_policy.as_deref() {
+            Some("together") | None => GreetingPolicy::Together,
+            Some("each") => GreetingPolicy::Each,
+            Some(policy) => return Err(ConfigError::GreetingPolicy(String::from(policy))),
+        };
         Ok(Config {
             token: raw.token,
             proxy: raw.proxy,
             webhook_url,
             redis_url: raw.redis_url,
             postgres_url: raw.postgres_url,
-            chat_id: raw.chat_id,
+            chat_ids: raw.chat_id,
+            timezone,
+            greeting_policy,
         })
     }
 
@@ -62,10 +83,22 @@ impl Config {
     }
 }
 
+/// Greeting policy for several members joined at once
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GreetingPolicy {
+    /// One greeting for all members
+    Together,
+    /// A greeting for each member
+    Each,
+}
+
 #[derive(Debug)]
 pub enum ConfigError {
     Envy(EnvyError),
+    GreetingPolicy(String),
+    NoChats,
     ProxyAddress(ParseProxyError),
+    Timezone(String),
     WebhookAddress(AddrParseError),
 }
 
@@ -87,6 +120,9 @@ impl Error for ConfigError {
             ConfigError::Envy(err) => Some(err),
             ConfigError::ProxyAddress(err) => Some(err),
             ConfigError::WebhookAddress(err) => Some(err),
+            ConfigError::GreetingPolicy(_) => None,
+            ConfigError::NoChats => None,
+            ConfigError::Timezone(_) => None,
         }
     }
 }
@@ -95,7 +131,10 @@ impl fmt::Display for ConfigError {
     fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ConfigError::Envy(err) => write!(out, "{}", err),
+            ConfigError::GreetingPolicy(policy) => write!(out, "unknown greeting policy: {}", policy),
+            ConfigError::NoChats => write!(out, "at least one chat id is required"),
             ConfigError::ProxyAddress(err) => write!(out, "bad proxy address: {}", err),
+            ConfigError::Timezone(name) => write!(out, "unknown timezone: {}", name),
             ConfigError::WebhookAddress(err) => write!(out, "bad webhook address: {}", err),
         }
     }
@@ -1,5 +1,6 @@
 use crate::{config::Config, sender::MessageSender};
 use carapax::Api;
+use darkredis::ConnectionPool as RedisPool;
 use reqwest::Client as HttpClient;
 use std::sync::Arc;
 use tokio_postgres::Client as PgClient;
@@ -11,4 +12,5 @@ pub struct Context {
     pub http_client: HttpClient,
     pub message_sender: MessageSender,
     pub pg_client: Arc<PgClient>,
+    pub redis_pool: RedisPool,
 }
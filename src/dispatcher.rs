@@ -1,12 +1,16 @@
 use crate::{
     context::Context,
     handler::{
-        autoresponse::AutoresponseHandler,
+        autoresponse::{AutoresponseHandler, HitCounter, Limiter, PhrasesCommand, RulesStore, WhyReplyCommand},
         ferris::handle_ferris,
         greetings::handle_new_chat_member,
+        remind::{list_reminders, remind, unremind},
+        schedule::ScheduleCommand,
         text::{replace_text_handler, TransformCommand},
         user::get_user_info,
     },
+    scheduler::Scheduler,
+    selection::Selector,
 };
 use carapax::{
     access::{AccessHandler, AccessRule, InMemoryAccessPolicy},
@@ -14,18 +18,27 @@ use carapax::{
     Dispatcher,
 };
 
-pub async fn create(context: Context, chat_id: Integer) -> Dispatcher<Context> {
+pub async fn create(context: Context, chat_ids: &[Integer], scheduler: Scheduler) -> Dispatcher<Context> {
     let pg_client = context.pg_client.clone();
+    let selector = Selector::new(context.redis_pool.clone());
+    let limiter = Limiter::new(context.redis_pool.clone());
     let mut dispatcher = Dispatcher::new(context);
-    dispatcher.add_handler(AccessHandler::new(
-        InMemoryAccessPolicy::default().push_rule(AccessRule::allow_chat(chat_id)),
-    ));
+    let access_policy = chat_ids
+        .iter()
+        .fold(InMemoryAccessPolicy::default(), |policy, &chat_id| {
+            policy.push_rule(AccessRule::allow_chat(chat_id))
+        });
+    dispatcher.add_handler(AccessHandler::new(access_policy));
     dispatcher.add_handler(handle_new_chat_member);
-    dispatcher.add_handler(
-        AutoresponseHandler::new(pg_client)
-            .await
-            .expect("Failed to create autoresponse handler"),
-    );
+    let hits = HitCounter::new(pg_client.clone());
+    tokio::spawn(hits.clone().run());
+    let rules_store = RulesStore::new(pg_client)
+        .await
+        .expect("Failed to load autoresponse rules");
+    dispatcher.add_handler(PhrasesCommand::new(rules_store.clone()));
+    dispatcher.add_handler(WhyReplyCommand::new(rules_store.clone()));
+    dispatcher.add_handler(ScheduleCommand::new(scheduler));
+    dispatcher.add_handler(AutoresponseHandler::new(rules_store, selector, limiter, hits));
     dispatcher.add_handler(replace_text_handler);
     dispatcher.add_handler(TransformCommand::arrow());
     dispatcher.add_handler(TransformCommand::cw());
@@ -35,6 +48,9 @@ pub async fn create(context: Context, chat_id: Integer) -> Dispatcher<Context> {
     dispatcher.add_handler(TransformCommand::square());
     dispatcher.add_handler(TransformCommand::star());
     dispatcher.add_handler(get_user_info);
+    dispatcher.add_handler(remind);
+    dispatcher.add_handler(list_reminders);
+    dispatcher.add_handler(unremind);
     dispatcher.add_handler(handle_ferris);
     dispatcher
 }
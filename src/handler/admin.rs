@@ -0,0 +1,15 @@
+use carapax::{
+    methods::GetChatMember,
+    types::{ChatMember, Message},
+    Api, ExecuteError,
+};
+
+/// Returns whether author of a message is an administrator of the chat
+pub async fn is_admin(api: &Api, message: &Message) -> Result<bool, ExecuteError> {
+    let user_id = match message.get_user() {
+        Some(user) => user.id,
+        None => return Ok(false),
+    };
+    let member = api.execute(GetChatMember::new(message.get_chat_id(), user_id)).await?;
+    Ok(matches!(member, ChatMember::Administrator(_) | ChatMember::Creator(_)))
+}
@@ -0,0 +1,102 @@
+use super::AutoresponseError;
+use carapax::types::{ForwardFrom, Integer, Message, MessageData};
+use std::{fmt, str::FromStr};
+
+/// Part of a message which can be matched by rules
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    /// Text of a message
+    Text,
+    /// Caption of a photo, video, document, audio or voice message
+    Caption,
+    /// Emoji of a sticker
+    StickerEmoji,
+    /// Name of a sticker set
+    StickerSet,
+}
+
+impl FromStr for Source {
+    type Err = AutoresponseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Ok(match raw {
+            "text" => Source::Text,
+            "caption" => Source::Caption,
+            "sticker_emoji" => Source::StickerEmoji,
+            "sticker_set" => Source::StickerSet,
+            _ => return Err(AutoresponseError::UnknownSource(String::from(raw))),
+        })
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            out,
+            "{}",
+            match self {
+                Source::Text => "text",
+                Source::Caption => "caption",
+                Source::StickerEmoji => "sticker_emoji",
+                Source::StickerSet => "sticker_set",
+            }
+        )
+    }
+}
+
+/// Message to find rules for
+#[derive(Debug)]
+pub struct Input<'a> {
+    pub chat_id: Integer,
+    pub parts: Vec<(Source, &'a str)>,
+    pub is_forwarded: bool,
+    /// Whether a message is sent or forwarded from a bot
+    pub is_from_bot: bool,
+}
+
+impl<'a> Input<'a> {
+    pub fn from_message(message: &'a Message) -> Self {
+        let mut parts = Vec::new();
+        match message.data {
+            MessageData::Text(ref text) => parts.push((Source::Text, text.data.as_str())),
+            MessageData::Sticker(ref sticker) => {
+                if let Some(ref emoji) = sticker.emoji {
+                    parts.push((Source::StickerEmoji, emoji.as_str()));
+                }
+                if let Some(ref set_name) = sticker.set_name {
+                    parts.push((Source::StickerSet, set_name.as_str()));
+                }
+            }
+            // Text of other messages is a caption
+            _ => {
+                if let Some(caption) = message.get_text() {
+                    parts.push((Source::Caption, caption.data.as_str()));
+                }
+            }
+        }
+        let is_forwarded_from_bot = match message.forward {
+            Some(ref forward) => match forward.from {
+                ForwardFrom::User(ref user) => user.is_bot,
+                _ => false,
+            },
+            None => false,
+        };
+        Self {
+            chat_id: message.get_chat_id(),
+            parts,
+            is_forwarded: message.forward.is_some(),
+            is_from_bot: is_forwarded_from_bot || message.get_user().map(|x| x.is_bot).unwrap_or(false),
+        }
+    }
+
+    /// Creates input of a text message
+    #[cfg(test)]
+    pub fn text(chat_id: Integer, text: &'a str) -> Self {
+        Self {
+            chat_id,
+            parts: vec![(Source::Text, text)],
+            is_forwarded: false,
+            is_from_bot: false,
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use carapax::types::Integer;
+use darkredis::{Command, ConnectionPool as RedisPool, Error as RedisError, Value};
+use rand::{thread_rng, Rng};
+
+const NAMESPACE: &str = "rustjerkbot:cooldown:phrase:";
+
+/// Limits how often a phrase replies
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Number of seconds a phrase is silent in a chat after a reply
+    pub cooldown: u32,
+    /// Number of seconds a phrase is silent for a user in a chat after a reply to this user
+    pub user_cooldown: u32,
+    /// Probability of a reply, from 0 to 1
+    pub probability: f64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            cooldown: 0,
+            user_cooldown: 0,
+            probability: 1.0,
+        }
+    }
+}
+
+/// Checks limits of phrases using cooldowns stored in Redis
+#[derive(Clone)]
+pub struct Limiter {
+    redis_pool: RedisPool,
+}
+
+impl Limiter {
+    pub fn new(redis_pool: RedisPool) -> Self {
+        Self { redis_pool }
+    }
+
+    /// Returns whether a phrase can reply and starts its cooldowns if so
+    ///
+    /// Phrase replies when cooldowns can not be checked.
+    ///
+    /// # Arguments
+    ///
+    /// * phrase_id - ID of a phrase
+    /// * limits - Limits of the phrase
+    /// * chat_id - Chat to reply in
+    /// * user_id - Author of an incoming message
+    pub async fn check(&self, phrase_id: i32, limits: Limits, chat_id: Integer, user_id: Option<Integer>) -> bool {
+        if !passes_probability(limits.probability, thread_rng().gen()) {
+            return false;
+        }
+        match self.acquire(&cooldown_keys(phrase_id, limits, chat_id, user_id)).await {
+            Ok(result) => result,
+            Err(err) => {
+                log::error!("failed to check cooldown of phrase {}: {}", phrase_id, err);
+                true
+            }
+        }
+    }
+
+    /// Sets every cooldown key which does not exist
+    ///
+    /// Returns false and removes keys set by this call when any of keys exists.
+    async fn acquire(&self, keys: &[(String, u32)]) -> Result<bool, RedisError> {
+        if keys.is_empty() {
+            return Ok(true);
+        }
+        let mut conn = self.redis_pool.get().await;
+        for (idx, (key, seconds)) in keys.iter().enumerate() {
+            // Key is set only when it does not exist, so concurrent messages can not both pass
+            let seconds = seconds.to_string();
+            let command = Command::new("SET")
+                .arg(key)
+                .arg(b"1")
+                .arg(b"EX")
+                .arg(&seconds)
+                .arg(b"NX");
+            if conn.run_command(command).await? == Value::Nil {
+                let acquired: Vec<&str> = keys[..idx].iter().map(|(key, _)| key.as_str()).collect();
+                if !acquired.is_empty() {
+                    conn.del_slice(&acquired).await?;
+                }
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Returns whether a reply passes a probability check
+///
+/// # Arguments
+///
+/// * probability - Probability of a reply, from 0 to 1
+/// * sample - Random number from 0 (inclusive) to 1 (exclusive)
+fn passes_probability(probability: f64, sample: f64) -> bool {
+    sample < probability
+}
+
+/// Returns keys of cooldowns with their durations in seconds, the user cooldown goes first
+fn cooldown_keys(phrase_id: i32, limits: Limits, chat_id: Integer, user_id: Option<Integer>) -> Vec<(String, u32)> {
+    let mut result = Vec::new();
+    if let Some(user_id) = user_id {
+        if limits.user_cooldown > 0 {
+            let key = format!("{}{}:{}:{}", NAMESPACE, phrase_id, chat_id, user_id);
+            result.push((key, limits.user_cooldown));
+        }
+    }
+    if limits.cooldown > 0 {
+        result.push((format!("{}{}:{}", NAMESPACE, phrase_id, chat_id), limits.cooldown));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let limits = Limits {
+            cooldown: 60,
+            user_cooldown: 600,
+            probability: 1.0,
+        };
+        assert_eq!(
+            cooldown_keys(1, limits, -100, Some(42)),
+            vec![
+                (String::from("rustjerkbot:cooldown:phrase:1:-100:42"), 600),
+                (String::from("rustjerkbot:cooldown:phrase:1:-100"), 60),
+            ]
+        );
+        assert_eq!(
+            cooldown_keys(1, limits, -100, None),
+            vec![(String::from("rustjerkbot:cooldown:phrase:1:-100"), 60)]
+        );
+        assert!(cooldown_keys(1, Limits::default(), -100, Some(42)).is_empty());
+    }
+
+    #[test]
+    fn probability() {
+        assert!(passes_probability(1.0, 0.0));
+        assert!(passes_probability(1.0, 0.999));
+        assert!(!passes_probability(0.0, 0.0));
+        assert!(passes_probability(0.3, 0.29));
+        assert!(!passes_probability(0.3, 0.3));
+    }
+}
@@ -0,0 +1,314 @@
+use self::template::{expand, Metadata};
+use crate::{
+    context::Context,
+    handler::admin::is_admin,
+    selection::{Selector, UnknownSelection},
+    sender::{ReplyTo, SendError},
+};
+use carapax::{
+    async_trait,
+    types::{Command, Message, ParseMode},
+    ExecuteError, Handler,
+};
+use chrono_tz::Tz;
+use regex::Error as RegexError;
+use std::{error::Error, fmt};
+use tokio_postgres::Error as PostgresError;
+
+/// Number of phrases in each list of `/phrases stats`
+const STATS_LIMIT: i64 = 10;
+
+const PREVIEW_LENGTH: usize = 30;
+
+/// Maximum number of rules in `/whyreply` answer
+const MAX_EXPLANATIONS: usize = 20;
+
+mod input;
+mod limits;
+mod output;
+mod rules;
+mod stats;
+mod store;
+mod template;
+
+pub use self::{
+    input::{Input, Source},
+    limits::Limiter,
+    output::Output,
+    rules::{RuleType, Rules, TextForm, Verdict},
+    stats::{get_stats, HitCounter, PhraseStats},
+    store::RulesStore,
+};
+
+pub struct AutoresponseHandler {
+    store: RulesStore,
+    selector: Selector,
+    limiter: Limiter,
+    hits: HitCounter,
+}
+
+impl AutoresponseHandler {
+    pub fn new(store: RulesStore, selector: Selector, limiter: Limiter, hits: HitCounter) -> Self {
+        Self {
+            store,
+            selector,
+            limiter,
+            hits,
+        }
+    }
+}
+
+#[async_trait]
+impl Handler<Context> for AutoresponseHandler {
+    type Input = Message;
+    type Output = Result<(), AutoresponseError>;
+
+    async fn handle(&mut self, context: &Context, message: Self::Input) -> Self::Output {
+        let input = Input::from_message(&message);
+        if input.parts.is_empty() {
+            return Ok(());
+        }
+        let rules = self.store.get().await;
+        let chat_id = input.chat_id;
+        let user_id = message.get_user().map(|x| x.id);
+        let metadata = Metadata::from_message(&message);
+        // Every reply to the message is tracked separately, so all of them are edited with the message
+        let mut position = 0;
+        for found in rules.find(&input) {
+            // Limited phrase does not reply, but still ends evaluation when it stops
+            if !found.messages.check_limits(&self.limiter, chat_id, user_id).await {
+                continue;
+            }
+            let mut reply = match found.messages.choose(&self.selector).await {
+                Some(reply) => reply,
+                None => continue,
+            };
+            if let Some(ref captures) = found.captures {
+                reply.output = reply.output.map_text(|x| expand(x, captures, &metadata));
+            }
+            let reply_to = if reply.reply_to {
+                ReplyTo::Reply
+            } else {
+                ReplyTo::Incoming
+            };
+            let sender = &context.message_sender;
+            match reply.output {
+                Output::Text(text) => sender.send_nth(&message, position, text, reply_to).await?,
+                Output::Media(media) => sender.send_media(&message, position, media, reply_to).await?,
+            }
+            position += 1;
+            self.hits.record(found.messages.id()).await;
+        }
+        Ok(())
+    }
+}
+
+/// Handles `/phrases` command
+///
+/// Available for chat administrators only.
+/// Supported subcommands:
+///
+/// * reload - Load autoresponse rules from database
+/// * stats - Show the most and the least used rules
+pub struct PhrasesCommand {
+    store: RulesStore,
+}
+
+impl PhrasesCommand {
+    pub fn new(store: RulesStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl Handler<Context> for PhrasesCommand {
+    type Input = Command;
+    type Output = Result<(), AutoresponseError>;
+
+    async fn handle(&mut self, context: &Context, command: Self::Input) -> Self::Output {
+        if command.get_name() != "/phrases" {
+            return Ok(());
+        }
+        let message = command.get_message();
+        let text = if !is_admin(&context.api, message)
+            .await
+            .map_err(AutoresponseError::GetChatMember)?
+        {
+            String::from("This command is available for administrators only")
+        } else {
+            match command.get_args().first().map(String::as_str) {
+                Some("reload") => match self.store.reload().await {
+                    Ok(count) => format!("Phrases reloaded: {}", count),
+                    Err(err) => {
+                        log::error!("failed to reload phrases: {}", err);
+                        format!("Failed to reload phrases: {}", err)
+                    }
+                },
+                Some("stats") => match get_stats(&context.pg_client, STATS_LIMIT).await {
+                    Ok((most, least)) => format!(
+                        "Most used phrases:\n{}\n\nLeast used phrases:\n{}",
+                        format_stats(&most, context.config.timezone),
+                        format_stats(&least, context.config.timezone)
+                    ),
+                    Err(err) => {
+                        log::error!("failed to get phrases stats: {}", err);
+                        String::from("Failed to get phrases stats")
+                    }
+                },
+                _ => String::from("Usage: /phrases reload|stats"),
+            }
+        };
+        context.message_sender.send(message, text, ReplyTo::Incoming).await?;
+        Ok(())
+    }
+}
+
+/// Handles `/whyreply` command
+///
+/// Available for chat administrators only.
+/// Explains which phrases match a message the command replies to.
+pub struct WhyReplyCommand {
+    store: RulesStore,
+}
+
+impl WhyReplyCommand {
+    pub fn new(store: RulesStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl Handler<Context> for WhyReplyCommand {
+    type Input = Command;
+    type Output = Result<(), AutoresponseError>;
+
+    async fn handle(&mut self, context: &Context, command: Self::Input) -> Self::Output {
+        if command.get_name() != "/whyreply" {
+            return Ok(());
+        }
+        let message = command.get_message();
+        let text = if !is_admin(&context.api, message)
+            .await
+            .map_err(AutoresponseError::GetChatMember)?
+        {
+            String::from("This command is available for administrators only")
+        } else {
+            match message.reply_to {
+                Some(ref reply_to) => {
+                    let rules = self.store.get().await;
+                    let explanations = rules.explain(&Input::from_message(reply_to));
+                    if explanations.is_empty() {
+                        String::from("No phrases match this message")
+                    } else {
+                        let mut lines: Vec<String> = explanations
+                            .iter()
+                            .take(MAX_EXPLANATIONS)
+                            .map(|x| ParseMode::Html.escape(x.to_string()))
+                            .collect();
+                        if explanations.len() > MAX_EXPLANATIONS {
+                            lines.push(format!("…and {} more", explanations.len() - MAX_EXPLANATIONS));
+                        }
+                        if explanations.iter().any(|x| x.verdict == Verdict::Replies) {
+                            lines.push(String::from("Cooldowns and probability are not checked"));
+                        }
+                        lines.join("\n")
+                    }
+                }
+                None => String::from("Reply to a message with /whyreply"),
+            }
+        };
+        context.message_sender.send(message, text, ReplyTo::Incoming).await?;
+        Ok(())
+    }
+}
+
+fn format_stats(items: &[PhraseStats], timezone: Tz) -> String {
+    if items.is_empty() {
+        return String::from("No phrases");
+    }
+    items
+        .iter()
+        .map(|item| {
+            let mut preview: String = item.input.chars().take(PREVIEW_LENGTH).collect();
+            if preview.len() < item.input.len() {
+                preview += "…";
+            }
+            let last_hit = match item.last_hit_at {
+                Some(time) => time.with_timezone(&timezone).format("%Y-%m-%d %H:%M").to_string(),
+                None => String::from("never"),
+            };
+            format!(
+                "{}: {} <code>{}</code> - {} hits, last {}",
+                item.id,
+                item.rule_type,
+                ParseMode::Html.escape(preview),
+                item.hits,
+                last_hit
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[derive(Debug)]
+pub enum AutoresponseError {
+    EmptyFileId,
+    GetChatMember(ExecuteError),
+    GetPhrases(PostgresError),
+    Regex(RegexError),
+    SaveHits(PostgresError),
+    Send(SendError),
+    StickerCaption,
+    UnknownRuleType(String),
+    UnknownSelection(UnknownSelection),
+    UnknownSource(String),
+}
+
+impl From<RegexError> for AutoresponseError {
+    fn from(err: RegexError) -> Self {
+        AutoresponseError::Regex(err)
+    }
+}
+
+impl From<UnknownSelection> for AutoresponseError {
+    fn from(err: UnknownSelection) -> Self {
+        AutoresponseError::UnknownSelection(err)
+    }
+}
+
+impl From<SendError> for AutoresponseError {
+    fn from(err: SendError) -> Self {
+        AutoresponseError::Send(err)
+    }
+}
+
+impl Error for AutoresponseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AutoresponseError::GetChatMember(err) => Some(err),
+            AutoresponseError::GetPhrases(err) => Some(err),
+            AutoresponseError::Regex(err) => Some(err),
+            AutoresponseError::SaveHits(err) => Some(err),
+            AutoresponseError::Send(err) => Some(err),
+            AutoresponseError::UnknownSelection(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AutoresponseError {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AutoresponseError::EmptyFileId => write!(out, "file ID of media output can not be empty"),
+            AutoresponseError::GetChatMember(err) => write!(out, "failed to get chat member: {}", err),
+            AutoresponseError::GetPhrases(err) => write!(out, "failed to get phrases: {}", err),
+            AutoresponseError::Regex(err) => write!(out, "failed to parse regex: {}", err),
+            AutoresponseError::SaveHits(err) => write!(out, "failed to save phrase hits: {}", err),
+            AutoresponseError::Send(err) => write!(out, "failed to send message: {}", err),
+            AutoresponseError::StickerCaption => write!(out, "sticker can not have a caption"),
+            AutoresponseError::UnknownRuleType(rule_type) => write!(out, "unknown rule type: {}", rule_type),
+            AutoresponseError::UnknownSelection(err) => write!(out, "{}", err),
+            AutoresponseError::UnknownSource(source) => write!(out, "unknown source: {}", source),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use super::AutoresponseError;
+use crate::sender::Media;
+
+/// Variant of phrase output
+///
+/// Media variants are written as `sticker:<file_id>`, `animation:<file_id> [caption]`
+/// and `photo:<file_id> [caption]`, anything else is a text.
+/// `text:` prefix makes a text which starts with one of media prefixes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+    Text(String),
+    Media(Media),
+}
+
+impl Output {
+    pub fn parse(raw: &str) -> Result<Self, AutoresponseError> {
+        let (kind, value) = match raw.find(':').map(|pos| (&raw[..pos], &raw[pos + 1..])) {
+            Some((kind @ "animation", value)) | Some((kind @ "photo", value)) | Some((kind @ "sticker", value)) => {
+                (kind, value.trim())
+            }
+            Some(("text", value)) => return Ok(Output::Text(String::from(value))),
+            _ => return Ok(Output::Text(String::from(raw))),
+        };
+        let (file_id, caption) = match value.find(char::is_whitespace) {
+            Some(pos) => (&value[..pos], Some(String::from(value[pos..].trim()))),
+            None => (value, None),
+        };
+        if file_id.is_empty() {
+            return Err(AutoresponseError::EmptyFileId);
+        }
+        let file_id = String::from(file_id);
+        Ok(Output::Media(match kind {
+            "animation" => Media::Animation { file_id, caption },
+            "photo" => Media::Photo { file_id, caption },
+            _ => {
+                if caption.is_some() {
+                    return Err(AutoresponseError::StickerCaption);
+                }
+                Media::Sticker { file_id }
+            }
+        }))
+    }
+
+    /// Applies a function to text or caption
+    pub fn map_text<F>(self, f: F) -> Self
+    where
+        F: Fn(&str) -> String,
+    {
+        match self {
+            Output::Text(text) => Output::Text(f(&text)),
+            Output::Media(Media::Animation { file_id, caption }) => Output::Media(Media::Animation {
+                file_id,
+                caption: caption.map(|x| f(&x)),
+            }),
+            Output::Media(Media::Photo { file_id, caption }) => Output::Media(Media::Photo {
+                file_id,
+                caption: caption.map(|x| f(&x)),
+            }),
+            media => media,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &str) -> Output {
+        Output::parse(raw).unwrap()
+    }
+
+    #[test]
+    fn parse_output() {
+        assert_eq!(parse("hello: world"), Output::Text(String::from("hello: world")));
+        assert_eq!(parse("text:sticker:x"), Output::Text(String::from("sticker:x")));
+        assert_eq!(
+            parse("sticker:CAAD"),
+            Output::Media(Media::Sticker {
+                file_id: String::from("CAAD")
+            })
+        );
+        assert_eq!(
+            parse("photo: AgAD <b>Ferris</b>\nis here"),
+            Output::Media(Media::Photo {
+                file_id: String::from("AgAD"),
+                caption: Some(String::from("<b>Ferris</b>\nis here"))
+            })
+        );
+        assert_eq!(
+            parse("animation:CgAD"),
+            Output::Media(Media::Animation {
+                file_id: String::from("CgAD"),
+                caption: None
+            })
+        );
+        assert!(Output::parse("photo: ").is_err());
+        assert!(Output::parse("sticker:CAAD caption").is_err());
+    }
+
+    #[test]
+    fn map_text() {
+        let upper = |x: &str| x.to_uppercase();
+        assert_eq!(parse("hi").map_text(upper), parse("HI"));
+        assert_eq!(parse("photo:AgAD hi").map_text(upper), parse("photo:AgAD HI"));
+        assert_eq!(parse("sticker:CAAD").map_text(upper), parse("sticker:CAAD"));
+    }
+}
@@ -0,0 +1,1007 @@
+use super::{
+    input::{Input, Source},
+    limits::{Limiter, Limits},
+    output::Output,
+    template::Captures,
+    AutoresponseError,
+};
+use crate::selection::{Selection, Selector};
+use aho_corasick::AhoCorasick;
+use carapax::types::Integer;
+use regex::{Error as RegexError, Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use std::{borrow::Cow, cmp::Reverse, collections::HashMap, fmt, str::FromStr};
+use tokio_postgres::Client as PgClient;
+
+/// Size limit of lazy DFA of `matches` rules, default limit is too small for hundreds of patterns
+const MAX_DFA_SIZE: usize = 64 * (1 << 20);
+
+/// Autoresponse rules for all chats
+///
+/// Rules are evaluated by priority (highest first), rules bound to a chat go before
+/// rules without a chat with the same priority, remaining ties are resolved by ID.
+/// Evaluation ends at the first matching rule unless the rule continues it.
+#[derive(Debug)]
+pub struct Rules {
+    items: Vec<Rule>,
+    index: Index,
+}
+
+impl Rules {
+    fn new(mut items: Vec<Rule>) -> Result<Self, AutoresponseError> {
+        items.sort_by_key(|x| (Reverse(x.priority), x.chat_id.is_none(), x.messages.id));
+        let index = Index::new(&items)?;
+        Ok(Self { items, index })
+    }
+
+    pub async fn load(pg_client: &PgClient) -> Result<Self, AutoresponseError> {
+        let mut items = Vec::new();
+        for row in pg_client
+            .query(
+                "SELECT id, input, rule_type, reply_to, output, chat_id, selection, priority, stop,
+                    ignore_case, whole_word, normalize, cooldown, user_cooldown, probability,
+                    sources, include_forwarded, include_bots
+                FROM autoresponse_phrases",
+                &[],
+            )
+            .await
+            .map_err(AutoresponseError::GetPhrases)?
+        {
+            let id: i32 = row.get(0);
+            let input: String = row.get(1);
+            let rule_type: RuleType = row.get::<_, String>(2).parse()?;
+            // A broken variant must not prevent other phrases from loading
+            let output = row
+                .get::<_, Vec<String>>(4)
+                .into_iter()
+                .filter_map(|x| match Output::parse(&x.replace("\\n", "\n")) {
+                    Ok(output) => Some(output),
+                    Err(err) => {
+                        log::warn!("skipping output {:?} of phrase {}: {}", x, id, err);
+                        None
+                    }
+                })
+                .collect();
+            let form = TextForm {
+                ignore_case: row.get(9),
+                normalize: row.get(11),
+            };
+            items.push(Rule {
+                matcher: Matcher::new(rule_type, &input, form)?,
+                form,
+                whole_word: row.get(10),
+                sources: row
+                    .get::<_, Vec<String>>(15)
+                    .iter()
+                    .map(|x| x.parse())
+                    .collect::<Result<_, _>>()?,
+                include_forwarded: row.get(16),
+                include_bots: row.get(17),
+                chat_id: row.get(5),
+                priority: row.get(7),
+                stop: row.get(8),
+                messages: Messages {
+                    id,
+                    reply_to: row.get(3),
+                    output,
+                    selection: row.get::<_, String>(6).parse()?,
+                    limits: Limits {
+                        cooldown: row.get::<_, i32>(12).max(0) as u32,
+                        user_cooldown: row.get::<_, i32>(13).max(0) as u32,
+                        probability: row.get(14),
+                    },
+                },
+            });
+        }
+        Self::new(items)
+    }
+
+    pub fn count(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns rules matching a message in evaluation order
+    pub fn find<'a>(&'a self, input: &Input) -> Vec<Found<'a>> {
+        // Every part of a message is prepared once for each form used by rules
+        let parts: Vec<(Source, PreparedText)> = input
+            .parts
+            .iter()
+            .map(|&(source, text)| {
+                let texts = self.index.forms.iter().map(|x| (x.form, x.form.apply(text))).collect();
+                (source, texts)
+            })
+            .collect();
+        let mut candidates = Vec::new();
+        for (source, texts) in &parts {
+            for (form_index, (_, text)) in self.index.forms.iter().zip(texts) {
+                let mut found = Vec::new();
+                form_index.find_plain(text, &mut found);
+                form_index.find_matches(text, &mut found);
+                candidates.extend(
+                    found
+                        .into_iter()
+                        .filter(|&idx| self.items[idx].sources.contains(source)),
+                );
+            }
+        }
+        // Indexes of rules are in evaluation order
+        candidates.sort_unstable();
+        candidates.dedup();
+        let mut result = Vec::new();
+        for idx in candidates {
+            let rule = &self.items[idx];
+            if !rule.accepts(input) {
+                continue;
+            }
+            let captures = match rule.matcher {
+                Matcher::Matches(ref regex) => {
+                    let captures = parts
+                        .iter()
+                        .zip(&input.parts)
+                        .filter(|((source, _), _)| rule.sources.contains(source))
+                        .find_map(|((_, texts), (_, original))| {
+                            // Regex of an `ignore_case` rule is case insensitive itself,
+                            // so groups keep case of the original text unless it is normalized
+                            let original = if rule.form.normalize {
+                                None
+                            } else {
+                                Captures::new(regex, original)
+                            };
+                            original.or_else(|| {
+                                let text = texts.iter().find(|(form, _)| *form == rule.form)?;
+                                Captures::new(regex, &text.1)
+                            })
+                        });
+                    match captures {
+                        Some(captures) => Some(captures),
+                        None => continue,
+                    }
+                }
+                _ => None,
+            };
+            result.push(Found {
+                messages: &rule.messages,
+                captures,
+            });
+            if rule.stop {
+                break;
+            }
+        }
+        result
+    }
+}
+
+impl Rules {
+    /// Explains matching of a message, every rule which matches any part of the message is reported
+    ///
+    /// Replying rules are the ones returned by `find()`, limits are not checked.
+    pub fn explain(&self, input: &Input) -> Vec<Explanation<'_>> {
+        let found: Vec<i32> = self.find(input).iter().map(|x| x.messages.id).collect();
+        // find() ends at the first stopping rule, so it is the last found one
+        let stopped_at = found
+            .last()
+            .copied()
+            .filter(|&id| self.items.iter().any(|x| x.messages.id == id && x.stop));
+        let mut result = Vec::new();
+        for rule in &self.items {
+            let source = match rule.find_part(input) {
+                Some(source) => source,
+                None => continue,
+            };
+            let verdict = if found.contains(&rule.messages.id) {
+                Verdict::Replies
+            } else if !rule.chat_id.map(|x| x == input.chat_id).unwrap_or(true) {
+                Verdict::OtherChat
+            } else if input.is_forwarded && !rule.include_forwarded {
+                Verdict::Forwarded
+            } else if input.is_from_bot && !rule.include_bots {
+                Verdict::Bot
+            } else {
+                match stopped_at {
+                    Some(id) => Verdict::Stopped(id),
+                    // Rule is not matched by find(), so it does not reply anyway
+                    None => continue,
+                }
+            };
+            result.push(Explanation {
+                id: rule.messages.id,
+                rule_type: rule.matcher.rule_type(),
+                input: rule.matcher.input(),
+                priority: rule.priority,
+                stop: rule.stop,
+                source,
+                verdict,
+            });
+        }
+        result
+    }
+}
+
+/// How a rule matches a message, see `Rules::explain()`
+#[derive(Debug)]
+pub struct Explanation<'a> {
+    pub id: i32,
+    pub rule_type: RuleType,
+    /// Input prepared by flags of the rule
+    pub input: &'a str,
+    pub priority: i32,
+    pub stop: bool,
+    /// Part of the message which matches
+    pub source: Source,
+    pub verdict: Verdict,
+}
+
+impl<'a> fmt::Display for Explanation<'a> {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            out,
+            "#{} {} {:?} (priority {}, {}) matches {}: ",
+            self.id,
+            self.rule_type,
+            self.input,
+            self.priority,
+            if self.stop { "stop" } else { "continue" },
+            self.source
+        )?;
+        match self.verdict {
+            Verdict::Replies => write!(out, "replies"),
+            Verdict::Stopped(id) => write!(out, "skipped, evaluation has ended at #{}", id),
+            Verdict::OtherChat => write!(out, "skipped, phrase is bound to another chat"),
+            Verdict::Forwarded => write!(out, "skipped, forwarded messages are ignored"),
+            Verdict::Bot => write!(out, "skipped, messages from bots are ignored"),
+        }
+    }
+}
+
+/// Whether a matching rule replies
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    Replies,
+    /// Evaluation has ended at a rule with given ID
+    Stopped(i32),
+    OtherChat,
+    Forwarded,
+    Bot,
+}
+
+/// Text of a message part prepared for each form
+type PreparedText<'a> = Vec<(TextForm, Cow<'a, str>)>;
+
+/// Rule matching a message
+pub struct Found<'a> {
+    pub messages: &'a Messages,
+    /// Groups captured by a `matches` rule
+    pub captures: Option<Captures<'a>>,
+}
+
+/// Finds rules matching a text, rules are grouped by form of text they expect
+#[derive(Debug)]
+struct Index {
+    forms: Vec<FormIndex>,
+}
+
+impl Index {
+    fn new(items: &[Rule]) -> Result<Self, AutoresponseError> {
+        let mut forms: Vec<TextForm> = Vec::new();
+        for rule in items {
+            if !forms.contains(&rule.form) {
+                forms.push(rule.form);
+            }
+        }
+        let forms = forms
+            .into_iter()
+            .map(|form| FormIndex::new(form, items))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { forms })
+    }
+}
+
+/// Finds rules of a form in one pass for each rule type
+#[derive(Debug)]
+struct FormIndex {
+    form: TextForm,
+    equals: HashMap<String, Vec<usize>>,
+    contains: AhoCorasick,
+    /// Indexes of rules for each pattern of `contains` automaton with their whole word flags
+    contains_rules: Vec<Vec<(usize, bool)>>,
+    matches: RegexIndex,
+    /// Index of a rule for each pattern of `matches` rules
+    matches_rules: Vec<usize>,
+}
+
+/// Patterns of `matches` rules
+#[derive(Debug)]
+enum RegexIndex {
+    Set(RegexSet),
+    /// Patterns are checked one by one when they are too big to be compiled together
+    Separate(Vec<Regex>),
+}
+
+impl FormIndex {
+    fn new(form: TextForm, items: &[Rule]) -> Result<Self, AutoresponseError> {
+        let mut equals: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut contains: HashMap<&str, Vec<(usize, bool)>> = HashMap::new();
+        let mut matches: Vec<&Regex> = Vec::new();
+        let mut matches_rules = Vec::new();
+        for (idx, rule) in items.iter().enumerate().filter(|(_, x)| x.form == form) {
+            match rule.matcher {
+                Matcher::Equals(ref input) => equals.entry(input.clone()).or_default().push(idx),
+                Matcher::Contains(ref input) => {
+                    contains.entry(input.as_str()).or_default().push((idx, rule.whole_word))
+                }
+                Matcher::Matches(ref regex) => {
+                    matches.push(regex);
+                    matches_rules.push(idx);
+                }
+            }
+        }
+        // Overlapping search reports every occurrence, duplicates are removed in find()
+        let (contains_patterns, contains_rules): (Vec<_>, Vec<_>) = contains.into_iter().unzip();
+        Ok(Self {
+            form,
+            equals,
+            contains: AhoCorasick::new_auto_configured(&contains_patterns),
+            contains_rules,
+            matches: match RegexSetBuilder::new(matches.iter().map(|x| x.as_str()))
+                .case_insensitive(form.ignore_case)
+                .dfa_size_limit(MAX_DFA_SIZE)
+                .build()
+            {
+                Ok(set) => RegexIndex::Set(set),
+                Err(RegexError::CompiledTooBig(limit)) => {
+                    log::warn!(
+                        "{} regex phrases exceed size limit of {} bytes, they are checked one by one",
+                        matches.len(),
+                        limit
+                    );
+                    RegexIndex::Separate(matches.into_iter().cloned().collect())
+                }
+                Err(err) => return Err(err.into()),
+            },
+            matches_rules,
+        })
+    }
+
+    /// Adds indexes of matching `matches` rules to result
+    ///
+    /// Text must be prepared using form of the index.
+    fn find_matches(&self, text: &str, result: &mut Vec<usize>) {
+        match self.matches {
+            RegexIndex::Set(ref set) => result.extend(set.matches(text).iter().map(|x| self.matches_rules[x])),
+            RegexIndex::Separate(ref items) => result.extend(
+                items
+                    .iter()
+                    .zip(&self.matches_rules)
+                    .filter(|(regex, _)| regex.is_match(text))
+                    .map(|(_, &idx)| idx),
+            ),
+        }
+    }
+
+    /// Adds indexes of matching `equals` and `contains` rules to result
+    ///
+    /// Text must be prepared using form of the index.
+    fn find_plain(&self, text: &str, result: &mut Vec<usize>) {
+        if let Some(rules) = self.equals.get(text) {
+            result.extend(rules);
+        }
+        for found in self.contains.find_overlapping_iter(text) {
+            let is_whole_word = is_whole_word(text, found.start(), found.end());
+            for &(idx, whole_word) in &self.contains_rules[found.pattern()] {
+                if !whole_word || is_whole_word {
+                    result.push(idx);
+                }
+            }
+        }
+    }
+}
+
+/// How text is prepared before it is matched
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextForm {
+    /// Text is converted to lowercase, `matches` rules are case insensitive
+    pub ignore_case: bool,
+    /// Punctuation is removed, whitespace is collapsed and trimmed
+    pub normalize: bool,
+}
+
+impl TextForm {
+    pub fn apply(self, text: &str) -> Cow<'_, str> {
+        let mut result = Cow::Borrowed(text);
+        if self.normalize {
+            result = Cow::Owned(normalize(&result));
+        }
+        if self.ignore_case {
+            result = Cow::Owned(result.to_lowercase());
+        }
+        result
+    }
+}
+
+/// Replaces punctuation with spaces, then collapses and trims whitespace
+fn normalize(text: &str) -> String {
+    text.split(|c: char| c.is_whitespace() || is_punctuation(c))
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation()
+        || matches!(
+            c,
+            '\u{a1}' | '\u{ab}' | '\u{bb}' | '\u{bf}' | '\u{2010}'..='\u{2027}' | '\u{2030}'..='\u{205e}' | '\u{3001}'..='\u{3003}'
+        )
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether a match is not surrounded by word characters
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    !matches!(text[..start].chars().next_back(), Some(c) if is_word_char(c))
+        && !matches!(text[end..].chars().next(), Some(c) if is_word_char(c))
+}
+
+#[derive(Debug)]
+struct Rule {
+    matcher: Matcher,
+    form: TextForm,
+    /// Whether `contains` rule matches whole words only
+    whole_word: bool,
+    /// Parts of a message the rule is matched with
+    sources: Vec<Source>,
+    include_forwarded: bool,
+    include_bots: bool,
+    chat_id: Option<Integer>,
+    priority: i32,
+    /// Whether evaluation ends when the rule matches
+    stop: bool,
+    messages: Messages,
+}
+
+impl Rule {
+    /// Whether a message can trigger the rule regardless of its parts
+    fn accepts(&self, input: &Input) -> bool {
+        self.chat_id.map(|x| x == input.chat_id).unwrap_or(true)
+            && (self.include_forwarded || !input.is_forwarded)
+            && (self.include_bots || !input.is_from_bot)
+    }
+
+    /// Returns the first part of a message matching the rule
+    fn find_part(&self, input: &Input) -> Option<Source> {
+        input
+            .parts
+            .iter()
+            .find(|(source, text)| self.sources.contains(source) && self.is_match(text))
+            .map(|(source, _)| *source)
+    }
+
+    /// Matches a text without index
+    fn is_match(&self, text: &str) -> bool {
+        let text = self.form.apply(text);
+        match self.matcher {
+            Matcher::Contains(ref input) => (0..=text.len()).any(|start| {
+                text.is_char_boundary(start)
+                    && text[start..].starts_with(input.as_str())
+                    && (!self.whole_word || is_whole_word(&text, start, start + input.len()))
+            }),
+            Matcher::Equals(ref input) => text == input.as_str(),
+            Matcher::Matches(ref regex) => regex.is_match(&text),
+        }
+    }
+}
+
+/// Input of a rule, `equals` and `contains` inputs are prepared the same way as text
+#[derive(Debug)]
+enum Matcher {
+    Contains(String),
+    Equals(String),
+    Matches(Regex),
+}
+
+impl Matcher {
+    fn rule_type(&self) -> RuleType {
+        match self {
+            Matcher::Contains(_) => RuleType::Contains,
+            Matcher::Equals(_) => RuleType::Equals,
+            Matcher::Matches(_) => RuleType::Matches,
+        }
+    }
+
+    fn input(&self) -> &str {
+        match self {
+            Matcher::Contains(input) | Matcher::Equals(input) => input,
+            Matcher::Matches(regex) => regex.as_str(),
+        }
+    }
+
+    fn new(rule_type: RuleType, input: &str, form: TextForm) -> Result<Self, AutoresponseError> {
+        Ok(match rule_type {
+            RuleType::Contains => Matcher::Contains(form.apply(input).into_owned()),
+            RuleType::Equals => Matcher::Equals(form.apply(input).into_owned()),
+            RuleType::Matches => Matcher::Matches(RegexBuilder::new(input).case_insensitive(form.ignore_case).build()?),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RuleType {
+    Contains,
+    Equals,
+    Matches,
+}
+
+impl RuleType {
+    /// Checks that a phrase with given input can be loaded by the handler
+    pub fn validate(self, input: &str) -> Result<(), AutoresponseError> {
+        if let RuleType::Matches = self {
+            Regex::new(input)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RuleType {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            out,
+            "{}",
+            match self {
+                RuleType::Contains => "contains",
+                RuleType::Equals => "equals",
+                RuleType::Matches => "matches",
+            }
+        )
+    }
+}
+
+impl FromStr for RuleType {
+    type Err = AutoresponseError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Ok(match raw {
+            "contains" => RuleType::Contains,
+            "equals" => RuleType::Equals,
+            "matches" => RuleType::Matches,
+            _ => return Err(AutoresponseError::UnknownRuleType(String::from(raw))),
+        })
+    }
+}
+
+/// Output of a phrase
+#[derive(Debug)]
+pub struct Messages {
+    id: i32,
+    reply_to: bool,
+    output: Vec<Output>,
+    selection: Selection,
+    limits: Limits,
+}
+
+impl Messages {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// Returns whether the phrase can reply now, see `Limiter::check()`
+    pub async fn check_limits(&self, limiter: &Limiter, chat_id: Integer, user_id: Option<Integer>) -> bool {
+        limiter.check(self.id, self.limits, chat_id, user_id).await
+    }
+
+    pub async fn choose(&self, selector: &Selector) -> Option<Reply> {
+        let key = format!("phrase:{}", self.id);
+        let index = selector.choose(self.selection, &key, self.output.len()).await?;
+        self.output.get(index).map(|output| Reply {
+            output: output.clone(),
+            reply_to: self.reply_to,
+        })
+    }
+}
+
+pub struct Reply {
+    pub output: Output,
+    pub reply_to: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::template::{expand, Metadata},
+        *,
+    };
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+    use std::time::Instant;
+
+    fn rule(id: i32, rule_type: RuleType, input: &str, chat_id: Option<Integer>, priority: i32, stop: bool) -> Rule {
+        Rule {
+            matcher: Matcher::new(rule_type, input, TextForm::default()).unwrap(),
+            form: TextForm::default(),
+            whole_word: false,
+            sources: vec![Source::Text, Source::Caption],
+            include_forwarded: true,
+            include_bots: true,
+            chat_id,
+            priority,
+            stop,
+            messages: Messages {
+                id,
+                reply_to: false,
+                output: vec![Output::Text(String::from(input))],
+                selection: Selection::Random,
+                limits: Limits::default(),
+            },
+        }
+    }
+
+    fn with_flags(
+        mut rule: Rule,
+        rule_type: RuleType,
+        input: &str,
+        ignore_case: bool,
+        whole_word: bool,
+        normalize: bool,
+    ) -> Rule {
+        rule.form = TextForm { ignore_case, normalize };
+        rule.matcher = Matcher::new(rule_type, input, rule.form).unwrap();
+        rule.whole_word = whole_word;
+        rule
+    }
+
+    fn find(rules: &Rules, chat_id: Integer, text: &str) -> Vec<i32> {
+        rules
+            .find(&Input::text(chat_id, text))
+            .into_iter()
+            .map(|x| x.messages.id)
+            .collect()
+    }
+
+    /// Matches every rule separately, the way rules were matched before the index
+    fn find_linear(rules: &Rules, chat_id: Integer, text: &str) -> Vec<i32> {
+        let mut result = Vec::new();
+        for rule in &rules.items {
+            if rule.accepts(&Input::text(chat_id, text)) && rule.is_match(text) {
+                result.push(rule.messages.id);
+                if rule.stop {
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    const WORDS: [&str; 18] = [
+        "rust",
+        "crab",
+        "go",
+        "borrow",
+        "checker",
+        "unsafe",
+        "ferris",
+        "cargo",
+        "trait",
+        "async",
+        "await",
+        "ru",
+        "st",
+        "lifetime",
+        "c++",
+        "rustacean",
+        "Rust",
+        "CRAB!",
+    ];
+
+    /// Creates rules of all types using words, about a third of them continue evaluation,
+    /// flags are random
+    fn generated_rules(count: usize) -> Rules {
+        let mut rng = StdRng::seed_from_u64(42);
+        let items = (0..count)
+            .map(|idx| {
+                let word = format!("{}{}", WORDS.choose(&mut rng).unwrap(), idx % 7);
+                let (rule_type, input) = match idx % 3 {
+                    0 => (RuleType::Contains, word),
+                    1 => (RuleType::Equals, word),
+                    _ => (RuleType::Matches, format!("(?i)^{0}|{0}!$", regex::escape(&word))),
+                };
+                let chat_id = if idx % 5 == 0 { Some(1) } else { None };
+                let item = rule(
+                    idx as i32,
+                    rule_type,
+                    &input,
+                    chat_id,
+                    rng.gen_range(0, 3),
+                    idx % 3 != 0,
+                );
+                with_flags(item, rule_type, &input, rng.gen(), rng.gen(), rng.gen())
+            })
+            .collect();
+        Rules::new(items).unwrap()
+    }
+
+    /// Words which are not used by generated rules
+    const OTHER_WORDS: [&str; 8] = [
+        "hello", "world", "python", "java", "weekend", "coffee", "meeting", "build",
+    ];
+
+    fn generated_texts(words: &[&str], count: usize) -> Vec<String> {
+        let mut rng = StdRng::seed_from_u64(7);
+        (0..count)
+            .map(|_| {
+                let len = rng.gen_range(1, 12);
+                (0..len)
+                    .map(|_| format!("{}{}", words.choose(&mut rng).unwrap(), rng.gen_range(0, 9)))
+                    .collect::<Vec<String>>()
+                    .join([" ", "", ", "].choose(&mut rng).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn order() {
+        let rules = Rules::new(vec![
+            rule(1, RuleType::Contains, "rust", None, 0, true),
+            rule(2, RuleType::Matches, "^rust", None, 10, true),
+            rule(3, RuleType::Equals, "rust", Some(1), 0, true),
+            rule(4, RuleType::Contains, "go", None, 0, true),
+            rule(5, RuleType::Contains, "ru", None, 0, true),
+        ])
+        .unwrap();
+        assert_eq!(find(&rules, 1, "rust"), vec![2]);
+        assert_eq!(find(&rules, 1, "i like rust"), vec![1]);
+        assert_eq!(find(&rules, 1, "i like ru"), vec![5]);
+        assert_eq!(find(&rules, 2, "go"), vec![4]);
+        assert_eq!(find(&rules, 2, "python"), Vec::<i32>::new());
+
+        let rules = Rules::new(vec![
+            rule(1, RuleType::Contains, "rust", None, 0, true),
+            rule(2, RuleType::Equals, "rust", Some(1), 0, true),
+        ])
+        .unwrap();
+        assert_eq!(find(&rules, 1, "rust"), vec![2]);
+        assert_eq!(find(&rules, 2, "rust"), vec![1]);
+    }
+
+    #[test]
+    fn big_regex_set() {
+        let pattern = r"^(\w{20})\s\w{20}";
+        let rules = Rules::new(
+            (1..=8)
+                .map(|id| rule(id, RuleType::Matches, pattern, None, 0, id == 8))
+                .collect(),
+        )
+        .unwrap();
+        assert!(matches!(rules.index.forms[0].matches, RegexIndex::Separate(_)));
+        let text = format!("{} {}", "a".repeat(20), "b".repeat(20));
+        assert_eq!(find(&rules, 1, &text), (1..=8).collect::<Vec<i32>>());
+        assert_eq!(find(&rules, 1, "a b"), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn same_as_linear() {
+        let rules = generated_rules(300);
+        for text in generated_texts(&WORDS, 2000) {
+            for &chat_id in &[1, 2] {
+                let found = find(&rules, chat_id, &text);
+                assert_eq!(found, find_linear(&rules, chat_id, &text), "{}", text);
+                let replies: Vec<i32> = rules
+                    .explain(&Input::text(chat_id, &text))
+                    .into_iter()
+                    .filter(|x| x.verdict == Verdict::Replies)
+                    .map(|x| x.id)
+                    .collect();
+                assert_eq!(found, replies, "{}", text);
+            }
+        }
+    }
+
+    /// Compares indexed and linear matching
+    ///
+    /// Run with `cargo test --release bench_find -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_find() {
+        let workloads = [
+            ("matching", generated_texts(&WORDS, 10_000)),
+            ("non-matching", generated_texts(&OTHER_WORDS, 10_000)),
+        ];
+        for &count in &[10, 100, 1000] {
+            let rules = generated_rules(count);
+            for (name, texts) in &workloads {
+                let start = Instant::now();
+                let linear: usize = texts.iter().map(|x| find_linear(&rules, 1, x).len()).sum();
+                let linear_time = start.elapsed();
+                let start = Instant::now();
+                let indexed: usize = texts.iter().map(|x| find(&rules, 1, x).len()).sum();
+                let indexed_time = start.elapsed();
+                assert_eq!(linear, indexed);
+                println!(
+                    "{} rules, {} {} texts: linear {:?}, indexed {:?}",
+                    count,
+                    texts.len(),
+                    name,
+                    linear_time,
+                    indexed_time
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn continue_evaluation() {
+        let rules = Rules::new(vec![
+            rule(1, RuleType::Contains, "rust", None, 0, true),
+            rule(2, RuleType::Contains, "rust", None, 1, false),
+            rule(3, RuleType::Contains, "rust", None, 0, true),
+        ])
+        .unwrap();
+        assert_eq!(find(&rules, 1, "rust"), vec![2, 1]);
+    }
+
+    #[test]
+    fn text_forms() {
+        let contains = |input, ignore_case, whole_word, normalize| {
+            let item = rule(1, RuleType::Contains, input, None, 0, true);
+            Rules::new(vec![with_flags(
+                item,
+                RuleType::Contains,
+                input,
+                ignore_case,
+                whole_word,
+                normalize,
+            )])
+            .unwrap()
+        };
+        let equals = |input, ignore_case, normalize| {
+            let item = rule(1, RuleType::Equals, input, None, 0, true);
+            Rules::new(vec![with_flags(
+                item,
+                RuleType::Equals,
+                input,
+                ignore_case,
+                false,
+                normalize,
+            )])
+            .unwrap()
+        };
+        let found = vec![1];
+        let not_found = Vec::<i32>::new();
+
+        let rules = contains("rust", false, false, false);
+        assert_eq!(find(&rules, 1, "i like Rust"), not_found);
+        assert_eq!(find(&rules, 1, "trust me"), found);
+
+        let rules = contains("Rust", true, false, false);
+        assert_eq!(find(&rules, 1, "i like RUST"), found);
+        assert_eq!(find(&rules, 1, "ПРИВЕТ rUsT"), found);
+
+        let rules = contains("rust", false, true, false);
+        assert_eq!(find(&rules, 1, "trust me"), not_found);
+        assert_eq!(find(&rules, 1, "rusty"), not_found);
+        assert_eq!(find(&rules, 1, "trust rust"), found);
+        assert_eq!(find(&rules, 1, "(rust)"), found);
+
+        let rules = contains("раст", true, true, false);
+        assert_eq!(find(&rules, 1, "Раст!"), found);
+        assert_eq!(find(&rules, 1, "растишка"), not_found);
+
+        let rules = equals("Hello, world!", true, true);
+        assert_eq!(find(&rules, 1, "  hello world "), found);
+        assert_eq!(find(&rules, 1, "HELLO... «WORLD»"), found);
+        assert_eq!(find(&rules, 1, "hello world again"), not_found);
+
+        let rules = equals("hello world", false, false);
+        assert_eq!(find(&rules, 1, "hello world!"), not_found);
+
+        let item = rule(1, RuleType::Matches, "^rust$", None, 0, true);
+        let rules = Rules::new(vec![with_flags(item, RuleType::Matches, "^rust$", true, false, true)]).unwrap();
+        assert_eq!(find(&rules, 1, " RUST?! "), found);
+    }
+
+    #[test]
+    fn captures() {
+        let rules = Rules::new(vec![
+            rule(1, RuleType::Matches, r"^(\w+) is (?P<what>\w+)$", None, 1, false),
+            rule(2, RuleType::Contains, "rust", None, 0, true),
+        ])
+        .unwrap();
+        let found = rules.find(&Input::text(1, "rust is great"));
+        assert_eq!(found.len(), 2);
+        let captures = found[0].captures.as_ref().unwrap();
+        let output = expand("$1 is not ${what}", captures, &Metadata::default());
+        assert_eq!(output, "rust is not great");
+        assert!(found[1].captures.is_none());
+
+        let regex = r"^(\w+) is (\w+)";
+        let expand_first = |ignore_case, normalize, text| {
+            let item = rule(1, RuleType::Matches, regex, None, 0, true);
+            let rules = Rules::new(vec![with_flags(
+                item,
+                RuleType::Matches,
+                regex,
+                ignore_case,
+                false,
+                normalize,
+            )])
+            .unwrap();
+            let found = rules.find(&Input::text(1, text));
+            expand("$1 $2", found[0].captures.as_ref().unwrap(), &Metadata::default())
+        };
+        assert_eq!(expand_first(true, false, "Rust IS Great"), "Rust Great");
+        assert_eq!(expand_first(false, true, "Rust is, Great!"), "Rust Great");
+        assert_eq!(expand_first(true, true, "Rust is, Great!"), "rust great");
+    }
+
+    #[test]
+    fn sources() {
+        let mut sticker = rule(1, RuleType::Equals, "🦀", None, 1, true);
+        sticker.sources = vec![Source::StickerEmoji];
+        let mut human = rule(2, RuleType::Matches, "^rust", None, 0, false);
+        human.include_forwarded = false;
+        human.include_bots = false;
+        let rules = Rules::new(vec![sticker, human, rule(3, RuleType::Contains, "rust", None, 0, true)]).unwrap();
+        let find = |input: &Input| {
+            rules
+                .find(input)
+                .into_iter()
+                .map(|x| x.messages.id)
+                .collect::<Vec<i32>>()
+        };
+
+        let mut input = Input::text(1, "🦀");
+        assert_eq!(find(&input), Vec::<i32>::new());
+        input.parts = vec![(Source::StickerEmoji, "🦀"), (Source::StickerSet, "rustacean")];
+        assert_eq!(find(&input), vec![1]);
+        input.parts = vec![(Source::StickerEmoji, "😀"), (Source::StickerSet, "rustacean")];
+        assert_eq!(find(&input), Vec::<i32>::new());
+
+        let mut input = Input::text(1, "rust is great");
+        assert_eq!(find(&input), vec![2, 3]);
+        input.parts = vec![(Source::Caption, "rust is great")];
+        assert_eq!(find(&input), vec![2, 3]);
+        input.is_forwarded = true;
+        assert_eq!(find(&input), vec![3]);
+        input.is_forwarded = false;
+        input.is_from_bot = true;
+        assert_eq!(find(&input), vec![3]);
+    }
+
+    #[test]
+    fn explain() {
+        let mut bound = rule(3, RuleType::Contains, "rust", Some(2), 2, true);
+        bound.include_bots = false;
+        let rules = Rules::new(vec![
+            rule(1, RuleType::Contains, "rust", None, 1, false),
+            rule(2, RuleType::Matches, "^rust", None, 0, true),
+            bound,
+            rule(4, RuleType::Equals, "rust is great", None, 0, true),
+            rule(5, RuleType::Contains, "go", None, 0, true),
+        ])
+        .unwrap();
+        let explain = |input: &Input| {
+            rules
+                .explain(input)
+                .into_iter()
+                .map(|x| (x.id, x.verdict))
+                .collect::<Vec<(i32, Verdict)>>()
+        };
+        let mut input = Input::text(1, "rust is great");
+        assert_eq!(
+            explain(&input),
+            vec![
+                (3, Verdict::OtherChat),
+                (1, Verdict::Replies),
+                (2, Verdict::Replies),
+                (4, Verdict::Stopped(2))
+            ]
+        );
+        input.chat_id = 2;
+        input.is_from_bot = true;
+        assert_eq!(explain(&input)[0], (3, Verdict::Bot));
+
+        let explanation = &rules.explain(&input)[1];
+        assert_eq!(
+            explanation.to_string(),
+            "#1 contains \"rust\" (priority 1, continue) matches text: replies"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use super::AutoresponseError;
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::Mutex, time::delay_for};
+use tokio_postgres::Client as PgClient;
+
+/// How often collected hits are saved
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Counts replies of phrases
+///
+/// Hits are collected in memory and saved to `autoresponse_phrases` in one query,
+/// hits which are not saved yet are lost when the bot stops.
+#[derive(Clone)]
+pub struct HitCounter {
+    pg_client: Arc<PgClient>,
+    hits: Arc<Mutex<HashMap<i32, Hit>>>,
+}
+
+impl HitCounter {
+    pub fn new(pg_client: Arc<PgClient>) -> Self {
+        Self {
+            pg_client,
+            hits: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn record(&self, phrase_id: i32) {
+        let hit = Hit {
+            count: 1,
+            last_hit_at: Utc::now(),
+        };
+        add_hit(&mut *self.hits.lock().await, phrase_id, hit);
+    }
+
+    async fn save(&self) -> Result<(), AutoresponseError> {
+        let hits = std::mem::take(&mut *self.hits.lock().await);
+        if hits.is_empty() {
+            return Ok(());
+        }
+        let mut ids = Vec::with_capacity(hits.len());
+        let mut counts = Vec::with_capacity(hits.len());
+        let mut times = Vec::with_capacity(hits.len());
+        for (id, hit) in &hits {
+            ids.push(*id);
+            counts.push(hit.count);
+            times.push(hit.last_hit_at);
+        }
+        let result = self
+            .pg_client
+            .execute(
+                "UPDATE autoresponse_phrases AS p
+                SET hits = p.hits + s.count, last_hit_at = s.last_hit_at
+                FROM unnest($1::int[], $2::bigint[], $3::timestamptz[]) AS s(id, count, last_hit_at)
+                WHERE p.id = s.id",
+                &[&ids, &counts, &times],
+            )
+            .await;
+        if let Err(err) = result {
+            // Hits are kept until the next attempt
+            let mut current = self.hits.lock().await;
+            for (id, hit) in hits {
+                add_hit(&mut current, id, hit);
+            }
+            return Err(AutoresponseError::SaveHits(err));
+        }
+        Ok(())
+    }
+
+    pub async fn run(self) {
+        loop {
+            delay_for(SAVE_INTERVAL).await;
+            if let Err(err) = self.save().await {
+                log::error!("{}", err);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Hit {
+    count: i64,
+    last_hit_at: DateTime<Utc>,
+}
+
+fn add_hit(hits: &mut HashMap<i32, Hit>, phrase_id: i32, hit: Hit) {
+    let current = hits.entry(phrase_id).or_insert(Hit { count: 0, ..hit });
+    current.count += hit.count;
+    current.last_hit_at = current.last_hit_at.max(hit.last_hit_at);
+}
+
+/// Usage of a phrase
+#[derive(Debug)]
+pub struct PhraseStats {
+    pub id: i32,
+    pub rule_type: String,
+    pub input: String,
+    pub hits: i64,
+    pub last_hit_at: Option<DateTime<Utc>>,
+}
+
+/// Returns the most used and the least used phrases
+///
+/// # Arguments
+///
+/// * pg_client - PostgreSQL client
+/// * limit - Number of phrases in each list
+pub async fn get_stats(
+    pg_client: &PgClient,
+    limit: i64,
+) -> Result<(Vec<PhraseStats>, Vec<PhraseStats>), AutoresponseError> {
+    let most = query_stats(pg_client, "hits DESC, last_hit_at DESC NULLS LAST, id", limit).await?;
+    let least = query_stats(pg_client, "hits, last_hit_at NULLS FIRST, id", limit).await?;
+    Ok((most, least))
+}
+
+async fn query_stats(pg_client: &PgClient, order: &str, limit: i64) -> Result<Vec<PhraseStats>, AutoresponseError> {
+    let query = format!(
+        "SELECT id, rule_type, input, hits, last_hit_at FROM autoresponse_phrases ORDER BY {} LIMIT $1",
+        order
+    );
+    Ok(pg_client
+        .query(query.as_str(), &[&limit])
+        .await
+        .map_err(AutoresponseError::GetPhrases)?
+        .into_iter()
+        .map(|row| PhraseStats {
+            id: row.get(0),
+            rule_type: row.get(1),
+            input: row.get(2),
+            hits: row.get(3),
+            last_hit_at: row.get(4),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn add() {
+        let hit = |count, second| Hit {
+            count,
+            last_hit_at: Utc.ymd(2020, 1, 1).and_hms(0, 0, second),
+        };
+        let mut hits = HashMap::new();
+        add_hit(&mut hits, 1, hit(1, 10));
+        add_hit(&mut hits, 1, hit(1, 20));
+        add_hit(&mut hits, 2, hit(1, 30));
+        // Hits restored after failed save are older than new ones
+        add_hit(&mut hits, 1, hit(5, 5));
+        assert_eq!(hits[&1], hit(7, 20));
+        assert_eq!(hits[&2], hit(1, 30));
+    }
+}
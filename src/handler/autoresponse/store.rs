@@ -0,0 +1,40 @@
+use super::{rules::Rules, AutoresponseError};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio_postgres::Client as PgClient;
+
+/// Shared storage for autoresponse rules
+///
+/// Rules are replaced as a whole, so a message which is being processed
+/// during reload keeps working with the set it has started with.
+#[derive(Clone)]
+pub struct RulesStore {
+    pg_client: Arc<PgClient>,
+    rules: Arc<RwLock<Arc<Rules>>>,
+}
+
+impl RulesStore {
+    pub async fn new(pg_client: Arc<PgClient>) -> Result<Self, AutoresponseError> {
+        let rules = Rules::load(&pg_client).await?;
+        Ok(Self {
+            pg_client,
+            rules: Arc::new(RwLock::new(Arc::new(rules))),
+        })
+    }
+
+    /// Returns current set of rules
+    pub async fn get(&self) -> Arc<Rules> {
+        self.rules.read().await.clone()
+    }
+
+    /// Loads rules from database and replaces current set
+    ///
+    /// Current set remains active when new rules can not be loaded.
+    /// Returns number of loaded rules.
+    pub async fn reload(&self) -> Result<usize, AutoresponseError> {
+        let rules = Rules::load(&self.pg_client).await?;
+        let count = rules.count();
+        *self.rules.write().await = Arc::new(rules);
+        Ok(count)
+    }
+}
@@ -0,0 +1,114 @@
+use crate::placeholders::{get_chat_title, replace_references, Unknown};
+use carapax::types::{Message, ParseMode};
+use regex::Regex;
+
+/// Groups captured by a `matches` rule
+#[derive(Debug)]
+pub struct Captures<'a> {
+    regex: &'a Regex,
+    groups: Vec<Option<String>>,
+}
+
+impl<'a> Captures<'a> {
+    /// Returns captures of a regex in a text or None if the regex does not match
+    pub fn new(regex: &'a Regex, text: &str) -> Option<Self> {
+        let groups = regex
+            .captures(text)?
+            .iter()
+            .map(|x| x.map(|x| String::from(x.as_str())))
+            .collect();
+        Some(Self { regex, groups })
+    }
+
+    /// Returns index of a group by its number or name
+    fn find_index(&self, name: &str) -> Option<usize> {
+        match name.parse::<usize>() {
+            Ok(idx) => Some(idx),
+            Err(_) => self.regex.capture_names().position(|x| x == Some(name)),
+        }
+    }
+}
+
+/// Values of an incoming message available in outputs
+#[derive(Debug, Default)]
+pub struct Metadata {
+    first_name: Option<String>,
+    username: Option<String>,
+    chat_title: Option<String>,
+}
+
+impl Metadata {
+    pub fn from_message(message: &Message) -> Self {
+        let user = message.get_user();
+        Self {
+            first_name: user.map(|x| x.first_name.clone()),
+            username: user.and_then(|x| x.username.clone()),
+            chat_title: get_chat_title(message).map(String::from),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        match name {
+            "first_name" => self.first_name.as_deref(),
+            "username" => self.username.as_deref(),
+            "chat_title" => self.chat_title.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Replaces references in output of a `matches` rule
+///
+/// References are written as `$name` or `${name}`, where name is a number or a name of a group
+/// or one of metadata values: `first_name`, `username` and `chat_title`.
+/// Groups take precedence over metadata, unknown references are replaced with an empty string.
+/// `$$` is replaced with `$`.
+///
+/// Groups of `ignore_case` rules keep case of the message, but groups of `normalize` rules
+/// are captured from normalized text (lowercase with `ignore_case`), see `TextForm`.
+///
+/// Output is HTML, so values are escaped.
+pub fn expand(output: &str, captures: &Captures, metadata: &Metadata) -> String {
+    replace_references(output, Unknown::Remove, |name| {
+        let value = match captures.find_index(name) {
+            Some(idx) => captures.groups.get(idx).and_then(Option::as_deref),
+            None => metadata.get(name),
+        };
+        value.map(|x| ParseMode::Html.escape(x))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_references() {
+        let regex = Regex::new(r"(?P<lang>\w+) is (\w+)( indeed)?").unwrap();
+        let captures = Captures::new(&regex, "so Go is slow").unwrap();
+        let metadata = Metadata {
+            first_name: Some(String::from("Ferris")),
+            username: None,
+            chat_title: Some(String::from("Rust & Co")),
+        };
+        let render = |output| expand(output, &captures, &metadata);
+        assert_eq!(render("$lang is not $2"), "Go is not slow");
+        assert_eq!(render("${1}lang ${2}er"), "Golang slower");
+        assert_eq!(render("[$0]"), "[Go is slow]");
+        assert_eq!(render("$3|$9|$unknown|${}"), "|||${}");
+        assert_eq!(
+            render("Hi, $first_name @$username in ${chat_title}"),
+            "Hi, Ferris @ in Rust &amp; Co"
+        );
+        assert_eq!(render("costs $$5, $"), "costs $5, $");
+        assert_eq!(render("<b>${lang</b>"), "<b>${lang</b>");
+        assert_eq!(render("caf\u{e9} $lang\u{e9}"), "caf\u{e9} Go\u{e9}");
+
+        let regex = Regex::new(r"^say (.+)$").unwrap();
+        let captures = Captures::new(&regex, "say <b>hi</b>").unwrap();
+        assert_eq!(
+            expand("<i>$1</i>", &captures, &metadata),
+            "<i>&lt;b&gt;hi&lt;/b&gt;</i>"
+        );
+    }
+}
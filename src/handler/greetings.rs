@@ -1,39 +1,99 @@
-use crate::context::Context;
+use crate::{
+    config::GreetingPolicy,
+    context::Context,
+    placeholders::{get_chat_title, replace_references, Unknown},
+};
 use carapax::{
     handler,
-    methods::SendMessage,
-    types::{Message, MessageData, ParseMode},
+    methods::{GetChatMembersCount, SendMessage},
+    types::{Integer, Message, MessageData, ParseMode, User},
     ExecuteError,
 };
 use std::{error::Error, fmt};
 use tokio_postgres::Error as PostgresError;
 
+/// Members are greeted together when more of them join at once, regardless of policy
+const MAX_SEPARATE_GREETINGS: usize = 5;
+
 #[handler]
 pub async fn handle_new_chat_member(context: &Context, input: Message) -> Result<(), NewChatMemberError> {
-    if let MessageData::NewChatMembers(_) = input.data {
-        let rows = context
-            .pg_client
-            .query("SELECT text FROM greetings ORDER BY RANDOM() LIMIT 1", &[])
-            .await
-            .map_err(NewChatMemberError::GetGreeting)?;
-        if rows.is_empty() {
-            return Ok(());
+    if let MessageData::NewChatMembers(ref users) = input.data {
+        let chat_id = input.get_chat_id();
+        let groups: Vec<&[User]> = match context.config.greeting_policy {
+            GreetingPolicy::Each if users.len() <= MAX_SEPARATE_GREETINGS => users.chunks(1).collect(),
+            _ => vec![users],
+        };
+        let mut member_count = None;
+        for members in groups {
+            let rows = context
+                .pg_client
+                .query(
+                    "SELECT text FROM greetings
+                    WHERE chat_id = $1 OR chat_id IS NULL
+                    ORDER BY RANDOM() LIMIT 1",
+                    &[&chat_id],
+                )
+                .await
+                .map_err(NewChatMemberError::GetGreeting)?;
+            if rows.is_empty() {
+                return Ok(());
+            }
+            let mut greeting: String = rows[0].get(0);
+            greeting = greeting.replace("\\n", "\n");
+            if member_count.is_none() && greeting.contains("member_count") {
+                match context.api.execute(GetChatMembersCount::new(chat_id)).await {
+                    Ok(count) => member_count = Some(count),
+                    Err(err) => log::warn!("failed to get members count: {}", err),
+                }
+            }
+            let greeting = render_greeting(
+                &greeting,
+                &Placeholders {
+                    members,
+                    chat_title: get_chat_title(&input),
+                    member_count,
+                },
+            );
+            context
+                .api
+                .execute(
+                    SendMessage::new(chat_id, greeting)
+                        .reply_to_message_id(input.id)
+                        .parse_mode(ParseMode::Html),
+                )
+                .await
+                .map_err(NewChatMemberError::SendMessage)?;
         }
-        let mut greeting: String = rows[0].get(0);
-        greeting = greeting.replace("\\n", "\n");
-        context
-            .api
-            .execute(
-                SendMessage::new(context.config.chat_id, greeting)
-                    .reply_to_message_id(input.id)
-                    .parse_mode(ParseMode::Html),
-            )
-            .await
-            .map_err(NewChatMemberError::SendMessage)?;
     }
     Ok(())
 }
 
+/// Values available in a greeting
+struct Placeholders<'a> {
+    members: &'a [User],
+    chat_title: Option<&'a str>,
+    member_count: Option<Integer>,
+}
+
+/// Replaces `${mention}`, `${first_name}`, `${chat_title}` and `${member_count}` in a greeting
+///
+/// Mentions and names of several members are joined with commas,
+/// other references are left as is, so existing greetings with `$` are not changed.
+fn render_greeting(greeting: &str, placeholders: &Placeholders) -> String {
+    let escape = |value: &str| ParseMode::Html.escape(value);
+    let join = |f: &dyn Fn(&User) -> String| placeholders.members.iter().map(f).collect::<Vec<String>>().join(", ");
+    replace_references(greeting, Unknown::Keep, |name| match name {
+        "mention" => Some(join(&|user| {
+            user.get_mention(ParseMode::Html)
+                .unwrap_or_else(|_| escape(&user.first_name))
+        })),
+        "first_name" => Some(join(&|user| escape(&user.first_name))),
+        "chat_title" => Some(placeholders.chat_title.map(escape).unwrap_or_default()),
+        "member_count" => Some(placeholders.member_count.map(|x| x.to_string()).unwrap_or_default()),
+        _ => None,
+    })
+}
+
 #[derive(Debug)]
 pub enum NewChatMemberError {
     GetGreeting(PostgresError),
@@ -57,3 +117,50 @@ impl fmt::Display for NewChatMemberError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: Integer, first_name: &str) -> User {
+        User {
+            id,
+            is_bot: false,
+            first_name: String::from(first_name),
+            last_name: None,
+            username: None,
+            language_code: None,
+        }
+    }
+
+    #[test]
+    fn render() {
+        let members = [user(1, "Ferris"), user(2, "<Corro>")];
+        let placeholders = Placeholders {
+            members: &members[..1],
+            chat_title: Some("Rust & Co"),
+            member_count: Some(42),
+        };
+        assert_eq!(
+            render_greeting(
+                "Hi, ${mention}! Welcome to ${chat_title}, you are #$member_count",
+                &placeholders
+            ),
+            r#"Hi, <a href="tg://user?id=1">Ferris</a>! Welcome to Rust &amp; Co, you are #42"#
+        );
+
+        let placeholders = Placeholders {
+            members: &members,
+            chat_title: None,
+            member_count: None,
+        };
+        assert_eq!(
+            render_greeting("Hi, $first_name${chat_title}${member_count}, $$1", &placeholders),
+            "Hi, Ferris, &lt;Corro&gt;, $1"
+        );
+        assert_eq!(
+            render_greeting("Pay $5 to ${bank} or $foo!", &placeholders),
+            "Pay $5 to ${bank} or $foo!"
+        );
+    }
+}
@@ -1,5 +1,8 @@
+pub mod admin;
 pub mod autoresponse;
 pub mod ferris;
 pub mod greetings;
+pub mod remind;
+pub mod schedule;
 pub mod text;
 pub mod user;
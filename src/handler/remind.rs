@@ -0,0 +1,184 @@
+use crate::{
+    context::Context,
+    reminders::parse_when,
+    sender::{ReplyTo, SendError},
+};
+use carapax::{
+    handler,
+    types::{Command, ParseMode},
+};
+use chrono::{DateTime, Utc};
+use std::{error::Error, fmt};
+use tokio_postgres::Error as PostgresError;
+
+const USAGE: &str = "Usage: /remind <when> <text>
+
+Examples:
+/remind in 2h check the oven
+/remind tomorrow 10:00 standup
+/remind 2026-11-01 18:00 release";
+
+/// Maximum number of pending reminders of a user in a chat
+const MAX_REMINDERS: i64 = 10;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+#[handler(command = "/remind")]
+pub async fn remind(context: &Context, command: Command) -> Result<(), RemindError> {
+    let message = command.get_message();
+    let user_id = match message.get_user() {
+        Some(user) => user.id,
+        None => return Ok(()),
+    };
+    let chat_id = message.get_chat_id();
+    let timezone = context.config.timezone;
+    let data = message.get_text().map(|x| x.data.as_str()).unwrap_or("");
+    // Skip command name, the rest is parsed preserving message formatting
+    let input = data.find(char::is_whitespace).map(|pos| &data[pos..]).unwrap_or("");
+    let text = match parse_when(input, &Utc::now().with_timezone(&timezone)) {
+        Some((_, text)) if text.trim().is_empty() => escape(USAGE),
+        Some((remind_at, _)) if remind_at <= Utc::now() => String::from("This time is in the past"),
+        Some((remind_at, text)) => {
+            let row = context
+                .pg_client
+                .query_one(
+                    "SELECT COUNT(*) FROM reminders WHERE chat_id = $1 AND user_id = $2",
+                    &[&chat_id, &user_id],
+                )
+                .await
+                .map_err(RemindError::GetReminders)?;
+            let count: i64 = row.get(0);
+            if count >= MAX_REMINDERS {
+                format!("You can not have more than {} reminders", MAX_REMINDERS)
+            } else {
+                let row = context
+                    .pg_client
+                    .query_one(
+                        "INSERT INTO reminders (chat_id, user_id, message_id, text, remind_at)
+                        VALUES ($1, $2, $3, $4, $5)
+                        RETURNING id",
+                        &[&chat_id, &user_id, &message.id, &text.trim(), &remind_at],
+                    )
+                    .await
+                    .map_err(RemindError::AddReminder)?;
+                let id: i32 = row.get(0);
+                format!(
+                    "Reminder {} is set for {}",
+                    id,
+                    remind_at.with_timezone(&timezone).format(TIME_FORMAT)
+                )
+            }
+        }
+        None => escape(USAGE),
+    };
+    context.message_sender.send(message, text, ReplyTo::Incoming).await?;
+    Ok(())
+}
+
+#[handler(command = "/reminders")]
+pub async fn list_reminders(context: &Context, command: Command) -> Result<(), RemindError> {
+    let message = command.get_message();
+    let user_id = match message.get_user() {
+        Some(user) => user.id,
+        None => return Ok(()),
+    };
+    let rows = context
+        .pg_client
+        .query(
+            "SELECT id, remind_at, text FROM reminders
+            WHERE chat_id = $1 AND user_id = $2
+            ORDER BY remind_at",
+            &[&message.get_chat_id(), &user_id],
+        )
+        .await
+        .map_err(RemindError::GetReminders)?;
+    let text = if rows.is_empty() {
+        String::from("You have no reminders")
+    } else {
+        rows.into_iter()
+            .map(|row| {
+                let id: i32 = row.get(0);
+                let remind_at: DateTime<Utc> = row.get(1);
+                let text: String = row.get(2);
+                format!(
+                    "{}: {} - {}",
+                    id,
+                    remind_at.with_timezone(&context.config.timezone).format(TIME_FORMAT),
+                    escape(&text)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+    context.message_sender.send(message, text, ReplyTo::Incoming).await?;
+    Ok(())
+}
+
+#[handler(command = "/unremind")]
+pub async fn unremind(context: &Context, command: Command) -> Result<(), RemindError> {
+    let message = command.get_message();
+    let user_id = match message.get_user() {
+        Some(user) => user.id,
+        None => return Ok(()),
+    };
+    let text = match command.get_args().first().and_then(|x| x.parse::<i32>().ok()) {
+        Some(id) => {
+            let count = context
+                .pg_client
+                .execute(
+                    "DELETE FROM reminders WHERE id = $1 AND chat_id = $2 AND user_id = $3",
+                    &[&id, &message.get_chat_id(), &user_id],
+                )
+                .await
+                .map_err(RemindError::RemoveReminder)?;
+            if count == 0 {
+                format!("Reminder {} not found", id)
+            } else {
+                format!("Reminder {} removed", id)
+            }
+        }
+        None => escape("Usage: /unremind <id>"),
+    };
+    context.message_sender.send(message, text, ReplyTo::Incoming).await?;
+    Ok(())
+}
+
+fn escape(input: &str) -> String {
+    ParseMode::Html.escape(input)
+}
+
+#[derive(Debug)]
+pub enum RemindError {
+    AddReminder(PostgresError),
+    GetReminders(PostgresError),
+    RemoveReminder(PostgresError),
+    Send(SendError),
+}
+
+impl From<SendError> for RemindError {
+    fn from(err: SendError) -> Self {
+        RemindError::Send(err)
+    }
+}
+
+impl Error for RemindError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RemindError::AddReminder(err) => Some(err),
+            RemindError::GetReminders(err) => Some(err),
+            RemindError::RemoveReminder(err) => Some(err),
+            RemindError::Send(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for RemindError {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemindError::AddReminder(err) => write!(out, "failed to add reminder: {}", err),
+            RemindError::GetReminders(err) => write!(out, "failed to get reminders: {}", err),
+            RemindError::RemoveReminder(err) => write!(out, "failed to remove reminder: {}", err),
+            RemindError::Send(err) => write!(out, "failed to send message: {}", err),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::{
+    context::Context,
+    handler::admin::is_admin,
+    scheduler::{ScheduleInfo, Scheduler},
+    sender::{ReplyTo, SendError},
+};
+use carapax::{
+    async_trait,
+    types::{Command, Integer, ParseMode},
+    ExecuteError, Handler,
+};
+use std::{error::Error, fmt};
+
+const USAGE: &str = "Usage:
+/schedule list
+/schedule add <cron> <message>
+/schedule remove <id>
+/schedule pause <id>
+/schedule resume <id>";
+
+const PREVIEW_LENGTH: usize = 50;
+
+pub struct ScheduleCommand {
+    scheduler: Scheduler,
+}
+
+impl ScheduleCommand {
+    pub fn new(scheduler: Scheduler) -> Self {
+        Self { scheduler }
+    }
+
+    async fn list(&self, chat_id: Integer) -> String {
+        match self.scheduler.list(chat_id).await {
+            Ok(items) if items.is_empty() => String::from("Schedule is empty"),
+            Ok(items) => items.iter().map(format_info).collect::<Vec<String>>().join("\n"),
+            Err(err) => {
+                log::error!("failed to get schedule: {}", err);
+                format!("Failed to get schedule: {}", escape(&err.to_string()))
+            }
+        }
+    }
+
+    async fn add(&self, chat_id: Integer, input: &str) -> String {
+        let fields = if input.trim_start().starts_with('@') { 1 } else { 5 };
+        let (cron, message) = match split_words(input, fields) {
+            Some((cron, message)) if !message.is_empty() => (cron.join(" "), message),
+            _ => return escape(USAGE),
+        };
+        match self.scheduler.add(chat_id, &cron, String::from(message)).await {
+            Ok(id) => format!("Schedule added: {}", id),
+            Err(err) => format!("Failed to add schedule: {}", escape(&err.to_string())),
+        }
+    }
+
+    async fn update(&self, chat_id: Integer, action: &str, id: Option<&String>) -> String {
+        let id = match id.and_then(|x| x.parse::<i32>().ok()) {
+            Some(id) => id,
+            None => return escape(USAGE),
+        };
+        let (result, done) = match action {
+            "remove" => (self.scheduler.remove(chat_id, id).await, "removed"),
+            "pause" => (self.scheduler.set_paused(chat_id, id, true).await, "paused"),
+            _ => (self.scheduler.set_paused(chat_id, id, false).await, "resumed"),
+        };
+        match result {
+            Ok(()) => format!("Schedule {}: {}", done, id),
+            Err(err) => format!("Failed to update schedule: {}", escape(&err.to_string())),
+        }
+    }
+}
+
+#[async_trait]
+impl Handler<Context> for ScheduleCommand {
+    type Input = Command;
+    type Output = Result<(), ScheduleCommandError>;
+
+    async fn handle(&mut self, context: &Context, command: Self::Input) -> Self::Output {
+        if command.get_name() != "/schedule" {
+            return Ok(());
+        }
+        let message = command.get_message();
+        let args = command.get_args();
+        let text = if !is_admin(&context.api, message)
+            .await
+            .map_err(ScheduleCommandError::GetChatMember)?
+        {
+            String::from("This command is available for administrators only")
+        } else {
+            match args.first().map(String::as_str) {
+                Some("list") => self.list(message.get_chat_id()).await,
+                Some("add") => {
+                    let data = message.get_text().map(|x| x.data.as_str()).unwrap_or("");
+                    // Skip command name and subcommand, the rest is parsed preserving message formatting
+                    let input = split_words(data, 2).map(|(_, rest)| rest).unwrap_or("");
+                    self.add(message.get_chat_id(), input).await
+                }
+                Some(action @ "remove") | Some(action @ "pause") | Some(action @ "resume") => {
+                    self.update(message.get_chat_id(), action, args.get(1)).await
+                }
+                _ => escape(USAGE),
+            }
+        };
+        context.message_sender.send(message, text, ReplyTo::Incoming).await?;
+        Ok(())
+    }
+}
+
+fn escape(input: &str) -> String {
+    ParseMode::Html.escape(input)
+}
+
+fn format_info(info: &ScheduleInfo) -> String {
+    let mut result = format!("{}: <code>{}</code>", info.id, escape(&info.schedule));
+    if let Some(ref timezone) = info.timezone {
+        result += &format!(" ({})", escape(timezone));
+    }
+    if info.paused {
+        result += ", paused";
+    }
+    if let Some(message) = info.messages.first() {
+        let preview: String = message.chars().take(PREVIEW_LENGTH).collect();
+        result += &format!(" - {}", escape(&preview));
+        if preview.len() < message.len() {
+            result += "…";
+        }
+        if info.messages.len() > 1 {
+            result += &format!(" (+{} more)", info.messages.len() - 1);
+        }
+    }
+    result
+}
+
+/// Splits input into first words and the rest of input
+///
+/// Returns None if input has less words than requested
+fn split_words(input: &str, count: usize) -> Option<(Vec<&str>, &str)> {
+    let mut words = Vec::with_capacity(count);
+    let mut rest = input;
+    for _ in 0..count {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        words.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    Some((words, rest.trim()))
+}
+
+#[derive(Debug)]
+pub enum ScheduleCommandError {
+    GetChatMember(ExecuteError),
+    Send(SendError),
+}
+
+impl From<SendError> for ScheduleCommandError {
+    fn from(err: SendError) -> Self {
+        ScheduleCommandError::Send(err)
+    }
+}
+
+impl Error for ScheduleCommandError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScheduleCommandError::GetChatMember(err) => Some(err),
+            ScheduleCommandError::Send(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for ScheduleCommandError {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScheduleCommandError::GetChatMember(err) => write!(out, "failed to get chat member: {}", err),
+            ScheduleCommandError::Send(err) => write!(out, "failed to send message: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split() {
+        assert_eq!(
+            split_words("/schedule add 0 9 * * mon Good\nmorning ", 2),
+            Some((vec!["/schedule", "add"], "0 9 * * mon Good\nmorning"))
+        );
+        assert_eq!(
+            split_words(" 0  9 * * mon <b>Good</b> morning", 5),
+            Some((vec!["0", "9", "*", "*", "mon"], "<b>Good</b> morning"))
+        );
+        assert_eq!(split_words("@daily", 1), Some((vec!["@daily"], "")));
+        assert_eq!(split_words("0 9 * *", 5), None);
+    }
+}
@@ -5,25 +5,31 @@ use carapax::{
 };
 use darkredis::ConnectionPool as RedisPool;
 use dotenv::dotenv;
-use env_logger;
 use reqwest::Client as HttpClient;
-use std::{env, sync::Arc, time::Duration};
+use std::{env, process, sync::Arc, time::Duration};
 use tokio_postgres::{connect as pg_connect, NoTls as PgNoTls};
 
 const SESSION_NAMESPACE: &str = "rustjerkbot:";
 const SESSION_GC_PERIOD: Duration = Duration::from_secs(3600);
 const SESSION_GC_TIMEOUT: Duration = Duration::from_secs(604_800);
 
+mod cli;
 mod config;
 mod context;
 mod db;
 mod dispatcher;
 mod handler;
+mod placeholders;
+mod reminders;
 mod scheduler;
+mod selection;
 mod sender;
 mod syndication;
 
-use self::{config::Config, context::Context, scheduler::Scheduler, sender::MessageSender, syndication::Syndication};
+use self::{
+    config::Config, context::Context, reminders::Reminders, scheduler::Scheduler, sender::MessageSender,
+    syndication::Syndication,
+};
 
 #[tokio::main]
 async fn main() {
@@ -50,6 +56,12 @@ async fn main() {
                     .await
                     .expect("Failed to run migrations");
             }
+            "phrases" => {
+                if let Err(err) = cli::phrases::run(&mut pg_client, args.collect()).await {
+                    eprintln!("{}", err);
+                    process::exit(1);
+                }
+            }
             _ => {
                 println!("Unknown command: {}", command);
             }
@@ -60,12 +72,11 @@ async fn main() {
 
             let pg_client = Arc::new(pg_client);
 
-            let session_backend = RedisSessionBackend::new(
-                SESSION_NAMESPACE,
-                RedisPool::create(config.redis_url.clone(), None, num_cpus::get())
-                    .await
-                    .expect("Redis connection failed"),
-            );
+            let redis_pool = RedisPool::create(config.redis_url.clone(), None, num_cpus::get())
+                .await
+                .expect("Redis connection failed");
+
+            let session_backend = RedisSessionBackend::new(SESSION_NAMESPACE, redis_pool.clone());
 
             let mut session_collector =
                 SessionCollector::new(session_backend.clone(), SESSION_GC_PERIOD, SESSION_GC_TIMEOUT);
@@ -77,19 +88,19 @@ async fn main() {
                 http_client: HttpClient::new(),
                 message_sender: MessageSender::new(api.clone(), SessionManager::new(session_backend)),
                 pg_client: pg_client.clone(),
+                redis_pool,
             };
 
             let scheduler = Scheduler::new(context.clone());
             scheduler.spawn().await.expect("Failed to spawn messages scheduler");
 
             let syndication = Syndication::new(context.clone());
-            tokio::spawn(async move {
-                if let Err(err) = syndication.run().await {
-                    log::error!("syndication error: {}", err);
-                }
-            });
+            tokio::spawn(syndication.run());
+
+            let reminders = Reminders::new(context.clone());
+            tokio::spawn(reminders.run());
 
-            let dispatcher = dispatcher::create(context, config.chat_id).await;
+            let dispatcher = dispatcher::create(context, &config.chat_ids, scheduler).await;
 
             match config.webhook_url {
                 Some((addr, path)) => {
@@ -0,0 +1,83 @@
+use carapax::types::{Message, MessageKind};
+
+/// What to do with references without a value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unknown {
+    /// Replace with an empty string
+    Remove,
+    /// Leave reference as is
+    Keep,
+}
+
+/// Replaces references in a text
+///
+/// References are written as `$name` or `${name}`, `$$` is replaced with `$`.
+///
+/// # Arguments
+///
+/// * text - Text with references
+/// * unknown - What to do with references without a value
+/// * get_value - Returns value of a reference by its name
+pub fn replace_references<F>(text: &str, unknown: Unknown, mut get_value: F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        if let Some(tail) = rest.strip_prefix('$') {
+            result.push('$');
+            rest = tail;
+            continue;
+        }
+        let (name, tail) = match parse_name(rest) {
+            Some(reference) => reference,
+            None => {
+                result.push('$');
+                continue;
+            }
+        };
+        match get_value(name) {
+            Some(value) => result += &value,
+            None if unknown == Unknown::Keep => {
+                result.push('$');
+                result += &rest[..rest.len() - tail.len()];
+            }
+            None => {}
+        }
+        rest = tail;
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Returns name of a reference and the rest of text
+fn parse_name(input: &str) -> Option<(&str, &str)> {
+    if let Some(input) = input.strip_prefix('{') {
+        let end = input.find('}')?;
+        if end == 0 {
+            return None;
+        }
+        Some((&input[..end], &input[end + 1..]))
+    } else {
+        let end = input
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(input.len());
+        if end == 0 {
+            return None;
+        }
+        Some(input.split_at(end))
+    }
+}
+
+/// Returns title of a chat where a message is sent, private chats have no title
+pub fn get_chat_title(message: &Message) -> Option<&str> {
+    match message.kind {
+        MessageKind::Channel { ref chat, .. } => Some(&chat.title),
+        MessageKind::Group { ref chat, .. } => Some(&chat.title),
+        MessageKind::Supergroup { ref chat, .. } => Some(&chat.title),
+        MessageKind::Private { .. } => None,
+    }
+}
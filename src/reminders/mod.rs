@@ -0,0 +1,137 @@
+use crate::context::Context;
+use carapax::{methods::SendMessage, types::Integer, ExecuteError};
+use std::{error::Error, fmt, time::Duration};
+use tokio::time::delay_for;
+use tokio_postgres::Error as PostgresError;
+
+mod when;
+
+pub use self::when::parse_when;
+
+/// How often due reminders are checked
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Reminders which can not be sent within this number of hours are removed
+const MAX_DELAY_HOURS: i32 = 24;
+
+/// Sends due reminders
+pub struct Reminders {
+    context: Context,
+}
+
+impl Reminders {
+    pub fn new(context: Context) -> Self {
+        Self { context }
+    }
+
+    async fn get_due(&self) -> Result<Vec<Reminder>, RemindersError> {
+        Ok(self
+            .context
+            .pg_client
+            .query(
+                "SELECT id, chat_id, message_id, text FROM reminders WHERE remind_at <= now() ORDER BY remind_at",
+                &[],
+            )
+            .await
+            .map_err(RemindersError::GetReminders)?
+            .into_iter()
+            .map(|row| Reminder {
+                id: row.get(0),
+                chat_id: row.get(1),
+                message_id: row.get(2),
+                text: row.get(3),
+            })
+            .collect())
+    }
+
+    async fn remove(&self, id: i32) -> Result<(), RemindersError> {
+        self.context
+            .pg_client
+            .execute("DELETE FROM reminders WHERE id = $1", &[&id])
+            .await
+            .map_err(RemindersError::RemoveReminder)?;
+        Ok(())
+    }
+
+    async fn remove_expired(&self) -> Result<u64, RemindersError> {
+        self.context
+            .pg_client
+            .execute(
+                "DELETE FROM reminders WHERE remind_at < now() - make_interval(hours => $1)",
+                &[&MAX_DELAY_HOURS],
+            )
+            .await
+            .map_err(RemindersError::RemoveReminder)
+    }
+
+    async fn send(&self, reminder: &Reminder) -> Result<(), RemindersError> {
+        let method = SendMessage::new(reminder.chat_id, reminder.text.clone()).reply_to_message_id(reminder.message_id);
+        if let Err(err) = self.context.api.execute(method).await {
+            // Original message may be deleted
+            log::warn!("failed to reply with reminder {}: {}", reminder.id, err);
+            self.context
+                .api
+                .execute(SendMessage::new(reminder.chat_id, reminder.text.clone()))
+                .await
+                .map_err(RemindersError::SendMessage)?;
+        }
+        Ok(())
+    }
+
+    async fn process(&self) -> Result<(), RemindersError> {
+        for reminder in self.get_due().await? {
+            match self.send(&reminder).await {
+                Ok(()) => self.remove(reminder.id).await?,
+                Err(err) => log::error!("failed to send reminder {}: {}", reminder.id, err),
+            }
+        }
+        let count = self.remove_expired().await?;
+        if count > 0 {
+            log::warn!("removed {} reminders which could not be sent", count);
+        }
+        Ok(())
+    }
+
+    pub async fn run(self) {
+        loop {
+            if let Err(err) = self.process().await {
+                log::error!("failed to process reminders: {}", err);
+            }
+            delay_for(POLL_INTERVAL).await;
+        }
+    }
+}
+
+struct Reminder {
+    id: i32,
+    chat_id: Integer,
+    message_id: Integer,
+    text: String,
+}
+
+#[derive(Debug)]
+pub enum RemindersError {
+    GetReminders(PostgresError),
+    RemoveReminder(PostgresError),
+    SendMessage(ExecuteError),
+}
+
+impl Error for RemindersError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RemindersError::GetReminders(err) => Some(err),
+            RemindersError::RemoveReminder(err) => Some(err),
+            RemindersError::SendMessage(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for RemindersError {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemindersError::GetReminders(err) => write!(out, "failed to get reminders: {}", err),
+            RemindersError::RemoveReminder(err) => write!(out, "failed to remove reminder: {}", err),
+            RemindersError::SendMessage(err) => write!(out, "failed to send message: {}", err),
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Maximum amount of units in a relative time, keeps date arithmetic from overflowing
+const MAX_AMOUNT: i64 = 100_000;
+
+/// Maximum relative time in days
+const MAX_DAYS: i64 = 3650;
+
+/// Parses time of a reminder from the beginning of input
+///
+/// Supported formats:
+///
+/// * `in 2h`, `in 1h30m`, `in 10 minutes` - Relative time (`w`, `d`, `h` and `m` units), up to 10 years
+/// * `today 18:00`, `tomorrow 10:00` - Time of today or tomorrow
+/// * `10:00` - Nearest time in future
+/// * `2026-11-01 18:00` - Date and time
+///
+/// Returns time and the rest of input.
+///
+/// # Arguments
+///
+/// * input - Input to parse
+/// * now - Current time, wall-clock times are interpreted in its timezone
+pub fn parse_when<'a>(input: &'a str, now: &DateTime<Tz>) -> Option<(DateTime<Utc>, &'a str)> {
+    let (first, rest) = next_word(input)?;
+    let first = first.to_lowercase();
+    let timezone = now.timezone();
+    let today = now.naive_local().date();
+    match first.as_str() {
+        "in" => {
+            let (amount, rest) = next_word(rest)?;
+            let (duration, rest) = match parse_compact_duration(amount) {
+                Some(duration) => (duration, rest),
+                None => {
+                    let amount = parse_amount(amount)?;
+                    let (unit, rest) = next_word(rest)?;
+                    (parse_unit(&unit.to_lowercase(), amount)?, rest)
+                }
+            };
+            if duration > ChronoDuration::days(MAX_DAYS) {
+                return None;
+            }
+            Some((now.with_timezone(&Utc).checked_add_signed(duration)?, rest))
+        }
+        "today" | "tomorrow" => {
+            let (time, rest) = next_word(rest)?;
+            let time = parse_time(time)?;
+            let date = if first == "today" { today } else { today.succ() };
+            Some((to_utc(&timezone, date.and_time(time))?, rest))
+        }
+        _ => {
+            if let Some(time) = parse_time(&first) {
+                let mut result = to_utc(&timezone, today.and_time(time))?;
+                if result <= now.with_timezone(&Utc) {
+                    result = to_utc(&timezone, today.succ().and_time(time))?;
+                }
+                Some((result, rest))
+            } else {
+                let date = NaiveDate::parse_from_str(&first, "%Y-%m-%d").ok()?;
+                let (time, rest) = next_word(rest)?;
+                Some((to_utc(&timezone, date.and_time(parse_time(time)?))?, rest))
+            }
+        }
+    }
+}
+
+/// Returns first word of input and the rest of input
+fn next_word(input: &str) -> Option<(&str, &str)> {
+    let input = input.trim_start();
+    if input.is_empty() {
+        return None;
+    }
+    let end = input.find(char::is_whitespace).unwrap_or(input.len());
+    Some((&input[..end], &input[end..]))
+}
+
+fn parse_amount(raw: &str) -> Option<i64> {
+    match raw.parse::<i64>() {
+        Ok(amount) if amount > 0 && amount <= MAX_AMOUNT => Some(amount),
+        _ => None,
+    }
+}
+
+fn parse_unit(unit: &str, amount: i64) -> Option<ChronoDuration> {
+    Some(match unit {
+        "m" | "min" | "mins" | "minute" | "minutes" => ChronoDuration::minutes(amount),
+        "h" | "hour" | "hours" => ChronoDuration::hours(amount),
+        "d" | "day" | "days" => ChronoDuration::days(amount),
+        "w" | "week" | "weeks" => ChronoDuration::weeks(amount),
+        _ => return None,
+    })
+}
+
+/// Parses a duration like `1h30m`
+fn parse_compact_duration(raw: &str) -> Option<ChronoDuration> {
+    let mut result = ChronoDuration::zero();
+    let mut rest = raw;
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| !c.is_ascii_digit())?;
+        let amount = parse_amount(&rest[..end])?;
+        let unit = rest[end..].chars().next()?;
+        result = result.checked_add(&parse_unit(&unit.to_string(), amount)?)?;
+        rest = &rest[end + unit.len_utf8()..];
+    }
+    if result.is_zero() {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+fn parse_time(raw: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(raw, "%H:%M").ok()
+}
+
+/// Converts wall-clock time to UTC, earliest time is used when DST makes it ambiguous
+fn to_utc(timezone: &Tz, datetime: NaiveDateTime) -> Option<DateTime<Utc>> {
+    timezone
+        .from_local_datetime(&datetime)
+        .earliest()
+        .map(|x| x.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Europe;
+
+    fn parse(input: &str) -> Option<(String, &str)> {
+        let now = Europe::Moscow.ymd(2020, 1, 1).and_hms(12, 0, 0);
+        parse_when(input, &now).map(|(time, rest)| {
+            let time = time.with_timezone(&Europe::Moscow).format("%Y-%m-%d %H:%M").to_string();
+            (time, rest.trim())
+        })
+    }
+
+    fn time(raw: &str, rest: &'static str) -> Option<(String, &'static str)> {
+        Some((String::from(raw), rest))
+    }
+
+    #[test]
+    fn relative() {
+        assert_eq!(parse("in 2h feed the crab"), time("2020-01-01 14:00", "feed the crab"));
+        assert_eq!(parse("in 1h30m"), time("2020-01-01 13:30", ""));
+        assert_eq!(parse("IN 10 Minutes x"), time("2020-01-01 12:10", "x"));
+        assert_eq!(parse("in 2 days x"), time("2020-01-03 12:00", "x"));
+        assert_eq!(parse("in 1w x"), time("2020-01-08 12:00", "x"));
+        assert_eq!(parse("in 0h x"), None);
+        assert_eq!(parse("in 2 parsecs"), None);
+        assert_eq!(parse("in 99999999999h"), None);
+        assert_eq!(parse("in 520w x"), time("2029-12-19 12:00", "x"));
+        assert_eq!(parse("in 530w x"), None);
+        assert_eq!(parse(&format!("in {} x", "100000w".repeat(140))), None);
+    }
+
+    #[test]
+    fn absolute() {
+        assert_eq!(parse("today 18:00 x"), time("2020-01-01 18:00", "x"));
+        assert_eq!(parse("tomorrow 10:00 x"), time("2020-01-02 10:00", "x"));
+        assert_eq!(parse("13:00 x"), time("2020-01-01 13:00", "x"));
+        assert_eq!(parse("11:00 x"), time("2020-01-02 11:00", "x"));
+        assert_eq!(parse("2026-11-01 18:00 x\ny"), time("2026-11-01 18:00", "x\ny"));
+        assert_eq!(parse("tomorrow x"), None);
+        assert_eq!(parse("2026-11-01"), None);
+        assert_eq!(parse("soon x"), None);
+        assert_eq!(parse(""), None);
+    }
+}
@@ -0,0 +1,460 @@
+use chrono::{
+    DateTime, Datelike, Duration as ChronoDuration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+    Timelike, Utc, Weekday,
+};
+use std::{error::Error, fmt, str::FromStr};
+
+/// How many days to look ahead when searching for a next fire time
+const MAX_LOOKAHEAD_DAYS: i64 = 366 * 5;
+
+/// Maximum length of a DST transition gap
+const MAX_TRANSITION_MINUTES: i64 = 24 * 60;
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A cron expression
+///
+/// Consists of 5 fields separated by whitespace: minute, hour, day of month, month and day of week.
+/// Each field accepts `*`, numbers, ranges (`1-5`), lists (`1,3,5`) and steps (`*/4`, `0-30/10`).
+/// Months and days of week also accept three-letter names (`jan`, `mon`),
+/// Sunday is either `0` or `7`.
+/// Day of week accepts `weekday#n` to select n-th weekday of a month (`mon#1` is the first Monday).
+/// When both day of month and day of week are restricted, a day matching either of them is used.
+///
+/// Shortcuts `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are supported as well.
+#[derive(Clone, Debug)]
+pub struct Cron {
+    minutes: Field,
+    hours: Field,
+    days_of_month: Field,
+    months: Field,
+    days_of_week: Field,
+    /// Pairs of weekday (from sunday) and its number within a month
+    nth_weekdays: Vec<(u32, u32)>,
+}
+
+impl Cron {
+    /// Creates an expression firing every day or every given weekday at given time
+    pub fn at(weekday: Option<Weekday>, time: NaiveTime) -> Self {
+        let days_of_week = match weekday {
+            Some(weekday) => Field::single(weekday.num_days_from_sunday()),
+            None => Field::any(0, 6),
+        };
+        Self {
+            minutes: Field::single(time.minute()),
+            hours: Field::single(time.hour()),
+            days_of_month: Field::any(1, 31),
+            months: Field::any(1, 12),
+            days_of_week,
+            nth_weekdays: Vec::new(),
+        }
+    }
+
+    /// Returns a first fire time after given time
+    ///
+    /// Returns None if there is no such time within next 5 years
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = after.date().and_hms(after.hour(), after.minute(), 0) + ChronoDuration::minutes(1);
+        let start_date = start.date();
+        for offset in 0..MAX_LOOKAHEAD_DAYS {
+            let date = start_date + ChronoDuration::days(offset);
+            if !self.matches_date(date) {
+                continue;
+            }
+            for hour in self.hours.values() {
+                for minute in self.minutes.values() {
+                    let candidate = date.and_hms(hour, minute, 0);
+                    if candidate >= start {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns a first fire time after given instant
+    ///
+    /// Expression is evaluated using wall-clock time of given timezone.
+    /// A fire time skipped by a DST transition is moved to the end of the transition,
+    /// a fire time repeated by a DST transition fires once.
+    pub fn next_fire_after<T: TimeZone>(&self, timezone: &T, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut local = after.with_timezone(timezone).naive_local();
+        loop {
+            local = self.next_after(local)?;
+            let candidates = match timezone.from_local_datetime(&local) {
+                LocalResult::Single(x) => vec![x],
+                LocalResult::Ambiguous(earliest, latest) => vec![earliest, latest],
+                LocalResult::None => (1..=MAX_TRANSITION_MINUTES)
+                    .find_map(
+                        |x| match timezone.from_local_datetime(&(local + ChronoDuration::minutes(x))) {
+                            LocalResult::Single(x) | LocalResult::Ambiguous(x, _) => Some(x),
+                            LocalResult::None => None,
+                        },
+                    )
+                    .into_iter()
+                    .collect(),
+            };
+            if let Some(instant) = candidates
+                .into_iter()
+                .map(|x| x.with_timezone(&Utc))
+                .find(|x| *x > after)
+            {
+                return Some(instant);
+            }
+        }
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if !self.months.contains(date.month()) {
+            return false;
+        }
+        let weekday = date.weekday().num_days_from_sunday();
+        let week_number = (date.day() - 1) / 7 + 1;
+        let dom = self.days_of_month.contains(date.day());
+        let dow = self.days_of_week.contains(weekday)
+            || self
+                .nth_weekdays
+                .iter()
+                .any(|&(nth_weekday, nth)| nth_weekday == weekday && nth == week_number);
+        let dow_restricted = self.days_of_week.restricted || !self.nth_weekdays.is_empty();
+        match (self.days_of_month.restricted, dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = CronError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let raw = raw.trim();
+        let expanded = match raw {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            _ => raw,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(CronError::new(raw, "expected 5 fields"));
+        }
+        let mut nth_weekdays = Vec::new();
+        let mut days_of_week = Vec::new();
+        for item in fields[4].split(',') {
+            match item.find('#') {
+                Some(pos) => {
+                    let weekday =
+                        parse_value(&item[..pos], 0, 7, &WEEKDAY_NAMES).map_err(|e| CronError::new(raw, e))?;
+                    let nth = match item[pos + 1..].parse::<u32>() {
+                        Ok(nth) if (1..=5).contains(&nth) => nth,
+                        _ => return Err(CronError::new(raw, "weekday number must be in range 1-5")),
+                    };
+                    nth_weekdays.push((weekday % 7, nth));
+                }
+                None => days_of_week.push(item),
+            }
+        }
+        let mut days_of_week = if days_of_week.is_empty() {
+            Field::empty()
+        } else {
+            Field::parse(&days_of_week.join(","), 0, 7, &WEEKDAY_NAMES).map_err(|e| CronError::new(raw, e))?
+        };
+        if days_of_week.contains(7) {
+            days_of_week.bits |= 1;
+        }
+        Ok(Self {
+            minutes: Field::parse(fields[0], 0, 59, &[]).map_err(|e| CronError::new(raw, e))?,
+            hours: Field::parse(fields[1], 0, 23, &[]).map_err(|e| CronError::new(raw, e))?,
+            days_of_month: Field::parse(fields[2], 1, 31, &[]).map_err(|e| CronError::new(raw, e))?,
+            months: Field::parse(fields[3], 1, 12, &MONTH_NAMES).map_err(|e| CronError::new(raw, e))?,
+            days_of_week,
+            nth_weekdays,
+        })
+    }
+}
+
+/// A set of allowed values of a cron field
+#[derive(Clone, Debug)]
+struct Field {
+    bits: u64,
+    max: u32,
+    /// Whether field is not `*`
+    restricted: bool,
+}
+
+impl Field {
+    fn any(min: u32, max: u32) -> Self {
+        Self {
+            bits: (min..=max).fold(0, |bits, x| bits | 1 << x),
+            max,
+            restricted: false,
+        }
+    }
+
+    fn single(value: u32) -> Self {
+        Self {
+            bits: 1 << value,
+            max: value,
+            restricted: true,
+        }
+    }
+
+    fn empty() -> Self {
+        Self {
+            bits: 0,
+            max: 0,
+            restricted: true,
+        }
+    }
+
+    fn parse(raw: &str, min: u32, max: u32, names: &[&str]) -> Result<Self, String> {
+        if raw == "*" {
+            return Ok(Self::any(min, max));
+        }
+        let mut bits = 0;
+        for item in raw.split(',') {
+            let (range, step) = match item.find('/') {
+                Some(pos) => match item[pos + 1..].parse::<u32>() {
+                    Ok(step) if step > 0 => (&item[..pos], step),
+                    _ => return Err(format!("bad step: {}", item)),
+                },
+                None => (item, 1),
+            };
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else {
+                match range.find('-') {
+                    Some(pos) => (
+                        parse_value(&range[..pos], min, max, names)?,
+                        parse_value(&range[pos + 1..], min, max, names)?,
+                    ),
+                    None => {
+                        let start = parse_value(range, min, max, names)?;
+                        // `5/10` means "from 5 to max with step 10"
+                        (start, if item.contains('/') { max } else { start })
+                    }
+                }
+            };
+            if start > end {
+                return Err(format!("bad range: {}", item));
+            }
+            for value in (start..=end).step_by(step as usize) {
+                bits |= 1 << value;
+            }
+        }
+        Ok(Self {
+            bits,
+            max,
+            restricted: true,
+        })
+    }
+
+    fn contains(&self, value: u32) -> bool {
+        value <= self.max && self.bits & (1 << value) != 0
+    }
+
+    fn values(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..=self.max).filter(move |&x| self.contains(x))
+    }
+}
+
+fn parse_value(raw: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+    let lowercase = raw.to_lowercase();
+    let value = match names.iter().position(|&x| x == lowercase) {
+        // Month names start from 1, weekday names start from 0 (sunday)
+        Some(pos) => pos as u32 + min,
+        None => raw.parse::<u32>().map_err(|_| format!("bad value: {}", raw))?,
+    };
+    if value < min || value > max {
+        return Err(format!("value {} is out of range {}-{}", value, min, max));
+    }
+    Ok(value)
+}
+
+#[derive(Debug)]
+pub struct CronError {
+    expression: String,
+    reason: String,
+}
+
+impl CronError {
+    fn new<R: Into<String>>(expression: &str, reason: R) -> Self {
+        Self {
+            expression: String::from(expression),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Error for CronError {}
+
+impl fmt::Display for CronError {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        write!(out, "bad cron expression '{}': {}", self.expression, self.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::{Europe, Tz};
+
+    fn dt(raw: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn next(expression: &str, after: &str) -> String {
+        let cron: Cron = expression.parse().unwrap();
+        cron.next_after(dt(after)).unwrap().format("%Y-%m-%d %H:%M").to_string()
+    }
+
+    #[test]
+    fn every_day() {
+        assert_eq!(next("30 10 * * *", "2020-01-01 09:00"), "2020-01-01 10:30");
+        assert_eq!(next("30 10 * * *", "2020-01-01 10:30"), "2020-01-02 10:30");
+        assert_eq!(next("@daily", "2020-12-31 23:59"), "2021-01-01 00:00");
+    }
+
+    #[test]
+    fn every_4_hours() {
+        assert_eq!(next("0 */4 * * *", "2020-01-01 09:00"), "2020-01-01 12:00");
+        assert_eq!(next("0 */4 * * *", "2020-01-01 22:00"), "2020-01-02 00:00");
+    }
+
+    #[test]
+    fn weekdays() {
+        // 2020-01-03 is Friday
+        assert_eq!(next("0 9 * * mon-fri", "2020-01-03 09:00"), "2020-01-06 09:00");
+        assert_eq!(next("0 9 * * 1-5", "2020-01-03 08:00"), "2020-01-03 09:00");
+        assert_eq!(next("0 9 * * sun", "2020-01-03 08:00"), "2020-01-05 09:00");
+        assert_eq!(next("0 9 * * 7", "2020-01-03 08:00"), "2020-01-05 09:00");
+    }
+
+    #[test]
+    fn nth_weekday() {
+        // First Monday of February 2020 is 3rd
+        assert_eq!(next("0 10 * * mon#1", "2020-01-07 00:00"), "2020-02-03 10:00");
+        assert_eq!(next("0 10 * * fri#2,mon#1", "2020-01-07 00:00"), "2020-01-10 10:00");
+    }
+
+    #[test]
+    fn day_of_month_and_week() {
+        // Either 15th day or Monday
+        assert_eq!(next("0 0 15 * mon", "2020-01-07 00:00"), "2020-01-13 00:00");
+        assert_eq!(next("0 0 15 * mon", "2020-01-13 00:00"), "2020-01-15 00:00");
+    }
+
+    #[test]
+    fn months_and_lists() {
+        assert_eq!(next("0,30 12 1 jan,jul *", "2020-01-01 12:00"), "2020-01-01 12:30");
+        assert_eq!(next("0,30 12 1 jan,jul *", "2020-01-01 12:30"), "2020-07-01 12:00");
+        assert_eq!(next("15/20 * * * *", "2020-01-01 12:40"), "2020-01-01 12:55");
+    }
+
+    #[test]
+    fn legacy() {
+        let time = NaiveTime::from_hms(18, 0, 0);
+        let cron = Cron::at(Some(Weekday::Fri), time);
+        assert_eq!(cron.next_after(dt("2020-01-03 18:00")).unwrap(), dt("2020-01-10 18:00"));
+        let cron = Cron::at(None, time);
+        assert_eq!(cron.next_after(dt("2020-01-03 17:00")).unwrap(), dt("2020-01-03 18:00"));
+    }
+
+    fn fire(expression: &str, timezone: Tz, after: &str) -> String {
+        let cron: Cron = expression.parse().unwrap();
+        let after = DateTime::<Utc>::from_utc(dt(after), Utc);
+        cron.next_fire_after(&timezone, after)
+            .unwrap()
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    }
+
+    #[test]
+    fn timezone() {
+        assert_eq!(
+            fire("0 9 * * *", Europe::Moscow, "2020-01-01 00:00"),
+            "2020-01-01 06:00"
+        );
+        assert_eq!(
+            fire("0 9 * * *", Europe::Moscow, "2020-01-01 06:00"),
+            "2020-01-02 06:00"
+        );
+        assert_eq!(
+            fire("0 9 * * *", Europe::Berlin, "2020-07-01 00:00"),
+            "2020-07-01 07:00"
+        );
+        assert_eq!(
+            fire("0 9 * * *", Europe::Berlin, "2020-12-01 00:00"),
+            "2020-12-01 08:00"
+        );
+    }
+
+    #[test]
+    fn dst_gap() {
+        // 02:30 does not exist on 2020-03-29 in Berlin, clocks jump from 02:00 CET to 03:00 CEST
+        assert_eq!(
+            fire("30 2 * * *", Europe::Berlin, "2020-03-28 23:00"),
+            "2020-03-29 01:00"
+        );
+        assert_eq!(
+            fire("30 2 * * *", Europe::Berlin, "2020-03-29 01:00"),
+            "2020-03-30 00:30"
+        );
+        assert_eq!(
+            fire("0 3 * * *", Europe::Berlin, "2020-03-29 00:00"),
+            "2020-03-29 01:00"
+        );
+    }
+
+    #[test]
+    fn dst_overlap() {
+        // 02:30 happens twice on 2020-10-25 in Berlin, clocks go back from 03:00 CEST to 02:00 CET
+        assert_eq!(
+            fire("30 2 * * *", Europe::Berlin, "2020-10-24 23:00"),
+            "2020-10-25 00:30"
+        );
+        assert_eq!(
+            fire("30 2 * * *", Europe::Berlin, "2020-10-25 00:30"),
+            "2020-10-26 01:30"
+        );
+        assert_eq!(
+            fire("30 2 * * *", Europe::Berlin, "2020-10-25 01:10"),
+            "2020-10-25 01:30"
+        );
+    }
+
+    #[test]
+    fn never() {
+        let cron: Cron = "0 0 30 feb *".parse().unwrap();
+        assert!(cron.next_after(dt("2020-01-01 00:00")).is_none());
+    }
+
+    #[test]
+    fn errors() {
+        for expression in &[
+            "",
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "* * * * mon#6",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+        ] {
+            assert!(expression.parse::<Cron>().is_err(), "{}", expression);
+        }
+    }
+}
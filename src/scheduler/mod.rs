@@ -0,0 +1,552 @@
+use self::cron::{Cron, CronError};
+use crate::{
+    context::Context,
+    selection::{Selection, Selector, UnknownSelection},
+};
+use carapax::{
+    methods::SendMessage,
+    types::{Integer, ParseMode},
+    Api,
+};
+use chrono::{DateTime, Duration as ChronoDuration, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use std::{collections::HashMap, error::Error, fmt, str::FromStr, sync::Arc, time::Duration};
+use tokio::{
+    sync::{oneshot, Mutex},
+    task::JoinHandle,
+    time::delay_for,
+};
+use tokio_postgres::{Client as PgClient, Error as PostgresError, Row};
+
+mod cron;
+
+/// Maximum time to sleep before checking the clock again
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// A run is missed when it is detected later than this number of seconds after its fire time
+const MISSED_RUN_THRESHOLD: i64 = 60;
+
+const SELECT_SCHEDULE: &str =
+    "SELECT id, day, time, messages, chat_id, cron, timezone, last_fired_at, missed_runs, selection FROM schedule";
+
+/// Registry of running scheduled tasks
+#[derive(Clone)]
+pub struct Scheduler {
+    pg_client: Arc<PgClient>,
+    timezone: Tz,
+    task_factory: Arc<TaskFactory>,
+    tasks: Arc<Mutex<HashMap<i32, TaskHandle>>>,
+}
+
+impl Scheduler {
+    pub fn new(context: Context) -> Self {
+        Self {
+            pg_client: context.pg_client.clone(),
+            timezone: context.config.timezone,
+            task_factory: Arc::new(TaskFactory::new(
+                context.api,
+                context.pg_client,
+                Selector::new(context.redis_pool),
+                context.config.chat_ids,
+            )),
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Starts all tasks which are not paused
+    pub async fn spawn(&self) -> Result<(), SchedulerError> {
+        for row in self
+            .pg_client
+            .query(format!("{} WHERE NOT paused", SELECT_SCHEDULE).as_str(), &[])
+            .await
+            .map_err(SchedulerError::GetSchedule)?
+        {
+            let item = self.parse_row(&row)?;
+            self.start(item).await;
+        }
+        Ok(())
+    }
+
+    /// Returns schedule rows of a chat and common rows
+    pub async fn list(&self, chat_id: Integer) -> Result<Vec<ScheduleInfo>, SchedulerError> {
+        Ok(self
+            .pg_client
+            .query(
+                "SELECT id, cron, day, time, timezone, paused, messages FROM schedule
+                WHERE chat_id = $1 OR chat_id IS NULL
+                ORDER BY id",
+                &[&chat_id],
+            )
+            .await
+            .map_err(SchedulerError::GetSchedule)?
+            .into_iter()
+            .map(|row| {
+                let cron: Option<String> = row.get(1);
+                let day: Option<String> = row.get(2);
+                let time: Option<NaiveTime> = row.get(3);
+                ScheduleInfo {
+                    id: row.get(0),
+                    schedule: match (cron, day, time) {
+                        (Some(cron), _, _) => cron,
+                        (None, Some(day), Some(time)) => format!("{} {}", day, time.format("%H:%M")),
+                        _ => String::new(),
+                    },
+                    timezone: row.get(4),
+                    paused: row.get(5),
+                    messages: row.get(6),
+                }
+            })
+            .collect())
+    }
+
+    /// Adds a new schedule row for a chat and starts it
+    ///
+    /// Returns ID of the new row
+    pub async fn add(&self, chat_id: Integer, cron: &str, message: String) -> Result<i32, SchedulerError> {
+        cron.parse::<Cron>()?;
+        let row = self
+            .pg_client
+            .query_one(
+                "INSERT INTO schedule (cron, messages, chat_id) VALUES ($1, $2, $3) RETURNING id",
+                &[&cron, &vec![message], &chat_id],
+            )
+            .await
+            .map_err(SchedulerError::UpdateSchedule)?;
+        let id = row.get(0);
+        self.load(id).await?;
+        Ok(id)
+    }
+
+    /// Removes a schedule row of a chat and stops its task
+    pub async fn remove(&self, chat_id: Integer, id: i32) -> Result<(), SchedulerError> {
+        let count = self
+            .pg_client
+            .execute("DELETE FROM schedule WHERE id = $1 AND chat_id = $2", &[&id, &chat_id])
+            .await
+            .map_err(SchedulerError::UpdateSchedule)?;
+        if count == 0 {
+            return Err(SchedulerError::NotFound(id));
+        }
+        self.stop(id).await;
+        Ok(())
+    }
+
+    /// Pauses or resumes a schedule row of a chat
+    pub async fn set_paused(&self, chat_id: Integer, id: i32, paused: bool) -> Result<(), SchedulerError> {
+        let count = self
+            .pg_client
+            .execute(
+                "UPDATE schedule SET paused = $3 WHERE id = $1 AND chat_id = $2",
+                &[&id, &chat_id, &paused],
+            )
+            .await
+            .map_err(SchedulerError::UpdateSchedule)?;
+        if count == 0 {
+            return Err(SchedulerError::NotFound(id));
+        }
+        if paused {
+            self.stop(id).await;
+        } else {
+            self.load(id).await?;
+        }
+        Ok(())
+    }
+
+    async fn load(&self, id: i32) -> Result<(), SchedulerError> {
+        let row = self
+            .pg_client
+            .query_opt(format!("{} WHERE id = $1", SELECT_SCHEDULE).as_str(), &[&id])
+            .await
+            .map_err(SchedulerError::GetSchedule)?
+            .ok_or(SchedulerError::NotFound(id))?;
+        let item = self.parse_row(&row)?;
+        self.start(item).await;
+        Ok(())
+    }
+
+    fn parse_row(&self, row: &Row) -> Result<ScheduleItem, SchedulerError> {
+        let day: Option<String> = row.get(1);
+        let time: Option<NaiveTime> = row.get(2);
+        let cron: Option<String> = row.get(5);
+        let cron = match (cron, day, time) {
+            (Some(cron), _, _) => cron.parse()?,
+            (None, Some(day), Some(time)) => Cron::at(day.parse::<Day>()?.0, time),
+            _ => return Err(SchedulerError::MissingSchedule),
+        };
+        let timezone: Option<String> = row.get(6);
+        let timezone = match timezone {
+            Some(name) => name.parse().map_err(|_| SchedulerError::UnknownTimezone(name))?,
+            None => self.timezone,
+        };
+        let missed_runs: String = row.get(8);
+        let selection: String = row.get(9);
+        Ok(ScheduleItem {
+            id: row.get(0),
+            cron,
+            messages: row.get(3),
+            chat_id: row.get(4),
+            timezone,
+            last_fired_at: row.get(7),
+            missed_runs: missed_runs.parse()?,
+            selection: selection.parse()?,
+        })
+    }
+
+    /// Starts a task, a running task with the same ID is stopped
+    async fn start(&self, item: ScheduleItem) {
+        let id = item.id;
+        self.stop(id).await;
+        let task = self.task_factory.create(item);
+        let (stop, stopped) = oneshot::channel();
+        let handle = tokio::spawn(async move {
+            tokio::select! {
+                _ = task.run() => {}
+                _ = stopped => {}
+            }
+        });
+        self.tasks.lock().await.insert(id, TaskHandle { handle, stop });
+    }
+
+    async fn stop(&self, id: i32) {
+        let task = self.tasks.lock().await.remove(&id);
+        if let Some(task) = task {
+            // Task may be already finished, so error is ignored
+            let _ = task.stop.send(());
+            if let Err(err) = task.handle.await {
+                log::error!("failed to stop scheduled task {}: {}", id, err)
+            }
+        }
+    }
+}
+
+struct TaskHandle {
+    handle: JoinHandle<()>,
+    stop: oneshot::Sender<()>,
+}
+
+/// Schedule row to display
+#[derive(Debug)]
+pub struct ScheduleInfo {
+    pub id: i32,
+    /// Cron expression or day and time
+    pub schedule: String,
+    pub timezone: Option<String>,
+    pub paused: bool,
+    pub messages: Vec<String>,
+}
+
+#[derive(Debug)]
+struct ScheduleItem {
+    id: i32,
+    cron: Cron,
+    messages: Vec<String>,
+    chat_id: Option<Integer>,
+    timezone: Tz,
+    last_fired_at: Option<DateTime<Utc>>,
+    missed_runs: MissedRuns,
+    selection: Selection,
+}
+
+/// What to do with runs missed while the bot was down or the host was suspended
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MissedRuns {
+    /// Send one message for all missed runs
+    CatchUp,
+    /// Do not send anything
+    Skip,
+}
+
+impl MissedRuns {
+    /// Returns how to handle a run planned at `next` when it is detected at `now`
+    fn handle(self, next: DateTime<Utc>, now: DateTime<Utc>) -> Run {
+        if now.signed_duration_since(next) <= ChronoDuration::seconds(MISSED_RUN_THRESHOLD) {
+            Run::OnTime
+        } else {
+            match self {
+                MissedRuns::CatchUp => Run::CatchUp,
+                MissedRuns::Skip => Run::Skip,
+            }
+        }
+    }
+}
+
+/// How a run of a task is handled
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Run {
+    /// Message is sent, the run is saved as fired at its planned time
+    OnTime,
+    /// Message is sent, all runs up to now are saved as fired
+    CatchUp,
+    /// Message is not sent, all runs up to now are saved as fired
+    Skip,
+}
+
+impl FromStr for MissedRuns {
+    type Err = SchedulerError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Ok(match raw {
+            "catch_up" => MissedRuns::CatchUp,
+            "skip" => MissedRuns::Skip,
+            _ => return Err(SchedulerError::UnknownMissedRuns(String::from(raw))),
+        })
+    }
+}
+
+/// Value of `day` column: `*` or a weekday
+#[derive(Debug)]
+struct Day(Option<Weekday>);
+
+impl FromStr for Day {
+    type Err = UnknownDay;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        if raw == "*" {
+            Ok(Day(None))
+        } else {
+            Ok(Day(Some(match raw {
+                "mon" => Weekday::Mon,
+                "tue" => Weekday::Tue,
+                "wed" => Weekday::Wed,
+                "thu" => Weekday::Thu,
+                "fri" => Weekday::Fri,
+                "sat" => Weekday::Sat,
+                "sun" => Weekday::Sun,
+                _ => return Err(UnknownDay(String::from(raw))),
+            })))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownDay(String);
+
+impl Error for UnknownDay {}
+
+impl fmt::Display for UnknownDay {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        write!(out, "unknown day: {}", self.0)
+    }
+}
+
+struct TaskFactory {
+    api: Api,
+    pg_client: Arc<PgClient>,
+    selector: Selector,
+    chat_ids: Vec<Integer>,
+}
+
+impl TaskFactory {
+    fn new(api: Api, pg_client: Arc<PgClient>, selector: Selector, chat_ids: Vec<Integer>) -> Self {
+        Self {
+            api,
+            pg_client,
+            selector,
+            chat_ids,
+        }
+    }
+
+    fn create(&self, item: ScheduleItem) -> Task {
+        Task {
+            id: item.id,
+            cron: item.cron,
+            timezone: item.timezone,
+            last_fired_at: item.last_fired_at,
+            missed_runs: item.missed_runs,
+            selection: item.selection,
+            messages: item.messages,
+            api: self.api.clone(),
+            pg_client: self.pg_client.clone(),
+            selector: self.selector.clone(),
+            chat_ids: match item.chat_id {
+                Some(chat_id) => vec![chat_id],
+                None => self.chat_ids.clone(),
+            },
+        }
+    }
+}
+
+struct Task {
+    id: i32,
+    cron: Cron,
+    timezone: Tz,
+    last_fired_at: Option<DateTime<Utc>>,
+    missed_runs: MissedRuns,
+    selection: Selection,
+    messages: Vec<String>,
+    api: Api,
+    pg_client: Arc<PgClient>,
+    selector: Selector,
+    chat_ids: Vec<Integer>,
+}
+
+impl Task {
+    async fn get_message(&self) -> Option<String> {
+        let key = format!("schedule:{}", self.id);
+        let index = self.selector.choose(self.selection, &key, self.messages.len()).await?;
+        self.messages.get(index).cloned()
+    }
+
+    async fn send(&self) {
+        if let Some(message) = self.get_message().await {
+            for &chat_id in &self.chat_ids {
+                let method = SendMessage::new(chat_id, message.clone()).parse_mode(ParseMode::Html);
+                if let Err(err) = self.api.execute(method).await {
+                    log::error!("failed to send scheduled message: {}", err)
+                }
+            }
+        }
+    }
+
+    async fn save_last_fired_at(&self, last_fired_at: DateTime<Utc>) {
+        if let Err(err) = self
+            .pg_client
+            .execute(
+                "UPDATE schedule SET last_fired_at = $2 WHERE id = $1",
+                &[&self.id, &last_fired_at],
+            )
+            .await
+        {
+            log::error!("failed to save last fire time of scheduled task {}: {}", self.id, err)
+        }
+    }
+
+    /// Sleeps until given time
+    ///
+    /// Wall clock is checked at least once per [`MAX_DELAY`],
+    /// so clock changes and host suspension do not shift fire times.
+    async fn sleep_until(next: DateTime<Utc>) {
+        loop {
+            match next.signed_duration_since(Utc::now()).to_std() {
+                Ok(delay) if delay > Duration::from_secs(0) => delay_for(delay.min(MAX_DELAY)).await,
+                _ => return,
+            }
+        }
+    }
+
+    async fn run(self) {
+        let mut last = self.last_fired_at.unwrap_or_else(Utc::now);
+        loop {
+            let next = match self.cron.next_fire_after(&self.timezone, last) {
+                Some(next) => next,
+                None => {
+                    log::warn!("scheduled task {} will never run: {:?}", self.id, self.cron);
+                    return;
+                }
+            };
+            Self::sleep_until(next).await;
+            let now = Utc::now();
+            // All runs missed up to now are handled at once
+            last = match self.missed_runs.handle(next, now) {
+                Run::OnTime => {
+                    self.send().await;
+                    next
+                }
+                Run::CatchUp => {
+                    log::info!("catching up missed run of scheduled task {} at {}", self.id, next);
+                    self.send().await;
+                    now
+                }
+                Run::Skip => {
+                    log::info!("skipping missed run of scheduled task {} at {}", self.id, next);
+                    now
+                }
+            };
+            // Skipped runs are saved too, so they are not checked again after a restart
+            self.save_last_fired_at(last).await;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SchedulerError {
+    Cron(CronError),
+    GetSchedule(PostgresError),
+    MissingSchedule,
+    NotFound(i32),
+    UnknownDay(UnknownDay),
+    UnknownMissedRuns(String),
+    UnknownSelection(UnknownSelection),
+    UnknownTimezone(String),
+    UpdateSchedule(PostgresError),
+}
+
+impl From<CronError> for SchedulerError {
+    fn from(err: CronError) -> Self {
+        SchedulerError::Cron(err)
+    }
+}
+
+impl From<UnknownDay> for SchedulerError {
+    fn from(err: UnknownDay) -> Self {
+        SchedulerError::UnknownDay(err)
+    }
+}
+
+impl From<UnknownSelection> for SchedulerError {
+    fn from(err: UnknownSelection) -> Self {
+        SchedulerError::UnknownSelection(err)
+    }
+}
+
+impl Error for SchedulerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SchedulerError::Cron(err) => Some(err),
+            SchedulerError::GetSchedule(err) => Some(err),
+            SchedulerError::UpdateSchedule(err) => Some(err),
+            SchedulerError::UnknownDay(err) => Some(err),
+            SchedulerError::UnknownSelection(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SchedulerError {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchedulerError::Cron(err) => write!(out, "{}", err),
+            SchedulerError::GetSchedule(err) => write!(out, "unable to get schedule: {}", err),
+            SchedulerError::MissingSchedule => write!(out, "either cron or day and time must be specified"),
+            SchedulerError::NotFound(id) => write!(out, "schedule {} not found", id),
+            SchedulerError::UnknownDay(err) => write!(out, "{}", err),
+            SchedulerError::UnknownMissedRuns(policy) => write!(out, "unknown missed runs policy: {}", policy),
+            SchedulerError::UnknownSelection(err) => write!(out, "{}", err),
+            SchedulerError::UnknownTimezone(name) => write!(out, "unknown timezone: {}", name),
+            SchedulerError::UpdateSchedule(err) => write!(out, "unable to update schedule: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use chrono_tz::Europe;
+
+    fn utc(raw: &str) -> DateTime<Utc> {
+        Utc.datetime_from_str(raw, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn missed_runs() {
+        let cron: Cron = "0 9 * * *".parse().unwrap();
+        let last_fired_at = utc("2020-01-01 06:00:00");
+        let next = cron.next_fire_after(&Europe::Moscow, last_fired_at).unwrap();
+        assert_eq!(next, utc("2020-01-02 06:00:00"));
+
+        for &policy in &[MissedRuns::CatchUp, MissedRuns::Skip] {
+            assert_eq!(policy.handle(next, utc("2020-01-02 06:00:00")), Run::OnTime);
+            assert_eq!(policy.handle(next, utc("2020-01-02 06:01:00")), Run::OnTime);
+        }
+        let now = utc("2020-01-05 12:00:00");
+        assert_eq!(MissedRuns::CatchUp.handle(next, now), Run::CatchUp);
+        assert_eq!(MissedRuns::Skip.handle(next, now), Run::Skip);
+
+        // Missed runs are saved as fired at `now`, so the next run is the first one after it
+        assert_eq!(
+            cron.next_fire_after(&Europe::Moscow, now).unwrap(),
+            utc("2020-01-06 06:00:00")
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use darkredis::{ConnectionPool as RedisPool, Error as RedisError};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use std::{error::Error, fmt, str::FromStr};
+
+const NAMESPACE: &str = "rustjerkbot:shuffle:";
+
+/// Shuffle bags and last chosen variants expire when they are not used for this number of seconds,
+/// every choice refreshes the expiration
+const TTL: u32 = 3600 * 24 * 30;
+
+/// How a message is chosen among variants
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+    /// Independent random choice every time
+    Random,
+    /// Every variant is used once before any of them is repeated
+    Shuffle,
+}
+
+impl FromStr for Selection {
+    type Err = UnknownSelection;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Ok(match raw {
+            "random" => Selection::Random,
+            "shuffle" => Selection::Shuffle,
+            _ => return Err(UnknownSelection(String::from(raw))),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownSelection(String);
+
+impl Error for UnknownSelection {}
+
+impl fmt::Display for UnknownSelection {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        write!(out, "unknown selection: {}", self.0)
+    }
+}
+
+/// Chooses variants using shuffle bags stored in Redis
+#[derive(Clone)]
+pub struct Selector {
+    redis_pool: RedisPool,
+}
+
+impl Selector {
+    pub fn new(redis_pool: RedisPool) -> Self {
+        Self { redis_pool }
+    }
+
+    /// Returns index of a variant to use or None if there are no variants
+    ///
+    /// Random choice is used when a shuffle bag is not available.
+    ///
+    /// # Arguments
+    ///
+    /// * selection - Selection strategy
+    /// * key - Unique key of a set of variants (`schedule:1` for example)
+    /// * count - Number of variants
+    pub async fn choose(&self, selection: Selection, key: &str, count: usize) -> Option<usize> {
+        if count == 0 {
+            return None;
+        }
+        if let Selection::Shuffle = selection {
+            match self.next_from_bag(key, count).await {
+                Ok(index) => return Some(index),
+                Err(err) => log::error!("failed to get shuffle bag {}: {}", key, err),
+            }
+        }
+        Some(thread_rng().gen_range(0, count))
+    }
+
+    async fn next_from_bag(&self, key: &str, count: usize) -> Result<usize, RedisError> {
+        // Number of variants is a part of the key, so changed variants start a new bag
+        let bag_key = format!("{}{}:{}", NAMESPACE, key, count);
+        let last_key = format!("{}{}:last", NAMESPACE, key);
+        let mut conn = self.redis_pool.get().await;
+        let index = match conn.lpop(&bag_key).await?.and_then(parse_index) {
+            Some(index) if index < count => {
+                // Bag expires when it is not used, not when it was filled
+                conn.expire_seconds(&bag_key, TTL).await?;
+                index
+            }
+            _ => {
+                let last = conn.get(&last_key).await?.and_then(parse_index);
+                let mut bag = fill_bag(count, last).into_iter();
+                // Bag is never empty as count is positive
+                let index = bag.next().unwrap_or(0);
+                let rest: Vec<String> = bag.map(|x| x.to_string()).collect();
+                if !rest.is_empty() {
+                    conn.rpush_slice(&bag_key, &rest).await?;
+                    conn.expire_seconds(&bag_key, TTL).await?;
+                }
+                index
+            }
+        };
+        conn.set_and_expire_seconds(&last_key, index.to_string(), TTL).await?;
+        Ok(index)
+    }
+}
+
+fn parse_index(raw: Vec<u8>) -> Option<usize> {
+    String::from_utf8(raw).ok()?.parse().ok()
+}
+
+/// Returns shuffled indexes of variants
+///
+/// Last used variant is never the first one, so a variant is not repeated across bags.
+fn fill_bag(count: usize, last: Option<usize>) -> Vec<usize> {
+    let mut rng = thread_rng();
+    let mut bag: Vec<usize> = (0..count).collect();
+    bag.shuffle(&mut rng);
+    if count > 1 && last == Some(bag[0]) {
+        let other = rng.gen_range(1, count);
+        bag.swap(0, other);
+    }
+    bag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("random".parse::<Selection>().unwrap(), Selection::Random);
+        assert_eq!("shuffle".parse::<Selection>().unwrap(), Selection::Shuffle);
+        assert!("cycle".parse::<Selection>().is_err());
+    }
+
+    #[test]
+    fn bag() {
+        for _ in 0..100 {
+            let mut bag = fill_bag(3, Some(1));
+            assert_ne!(bag[0], 1);
+            bag.sort();
+            assert_eq!(bag, vec![0, 1, 2]);
+        }
+        assert_eq!(fill_bag(1, Some(0)), vec![0]);
+        assert_eq!(fill_bag(0, None), Vec::<usize>::new());
+    }
+}
@@ -1,7 +1,7 @@
 use carapax::{
-    methods::{EditMessageText, SendMessage},
+    methods::{EditMessageText, SendAnimation, SendMessage, SendPhoto, SendSticker},
     session::{backend::redis::RedisBackend as RedisSessionBackend, SessionError, SessionIdError, SessionManager},
-    types::{Message, ParseMode},
+    types::{InputFile, Integer, Message, ParseMode},
     Api, ExecuteError,
 };
 use std::{error::Error, fmt};
@@ -20,32 +20,52 @@ impl MessageSender {
         Self { api, session_manager }
     }
 
-    async fn send_new(&self, incoming_message: &Message, text: String, reply_to: ReplyTo) -> Result<(), SendError> {
+    async fn send_new(
+        &self,
+        incoming_message: &Message,
+        position: usize,
+        text: String,
+        reply_to: ReplyTo,
+    ) -> Result<(), SendError> {
         let chat_id = incoming_message.get_chat_id();
-        let reply_to_id = match reply_to {
-            ReplyTo::Incoming => incoming_message.id,
-            ReplyTo::Reply => match incoming_message.reply_to {
-                Some(ref reply_to) => reply_to.id,
-                None => incoming_message.id,
-            },
-        };
         let result_message = self
             .api
             .execute(
                 SendMessage::new(chat_id, text)
-                    .reply_to_message_id(reply_to_id)
+                    .reply_to_message_id(get_reply_to_id(incoming_message, reply_to))
                     .parse_mode(ParseMode::Html),
             )
             .await?;
+        self.track(incoming_message, position, TrackedKind::Text, result_message.id)
+            .await
+    }
 
+    /// Remembers a message sent in reply to incoming message
+    async fn track(
+        &self,
+        incoming_message: &Message,
+        position: usize,
+        kind: TrackedKind,
+        result_message_id: Integer,
+    ) -> Result<(), SendError> {
         let mut session = self.session_manager.get_session(incoming_message)?;
-        let key = format!("{}{}", TRACK_MESSAGE_PREFIX, incoming_message.id);
-        session.set(&key, &result_message.id).await?;
+        let key = get_track_key(incoming_message, position, kind);
+        session.set(&key, &result_message_id).await?;
         session.expire(key, TRACK_MESSAGE_TIMEOUT).await?;
-
         Ok(())
     }
 
+    /// Returns ID of a message of given kind sent in reply to incoming message
+    async fn get_tracked(
+        &self,
+        incoming_message: &Message,
+        position: usize,
+        kind: TrackedKind,
+    ) -> Result<Option<Integer>, SendError> {
+        let mut session = self.session_manager.get_session(incoming_message)?;
+        Ok(session.get(get_track_key(incoming_message, position, kind)).await?)
+    }
+
     /// Send a new or edit already sent message with given text
     ///
     /// # Arguments
@@ -53,21 +73,136 @@ impl MessageSender {
     /// * incoming_message - Message from update to track to
     /// * text - Text to send
     pub async fn send(&self, incoming_message: &Message, text: String, reply_to: ReplyTo) -> Result<(), SendError> {
+        self.send_nth(incoming_message, 0, text, reply_to).await
+    }
+
+    /// Send a new or edit already sent message with given text when there are several replies
+    ///
+    /// Media sent in reply to incoming message can not be replaced with text,
+    /// so nothing is sent when incoming message is edited after such a reply.
+    ///
+    /// # Arguments
+    ///
+    /// * incoming_message - Message from update to track to
+    /// * position - Number of the reply to incoming message starting from 0, each one is tracked separately
+    /// * text - Text to send
+    pub async fn send_nth(
+        &self,
+        incoming_message: &Message,
+        position: usize,
+        text: String,
+        reply_to: ReplyTo,
+    ) -> Result<(), SendError> {
         let chat_id = incoming_message.get_chat_id();
         if incoming_message.is_edited() {
-            let mut session = self.session_manager.get_session(incoming_message)?;
-            let key = format!("{}{}", TRACK_MESSAGE_PREFIX, incoming_message.id);
-            let tracked_message_id = session.get(key).await?;
-            if let Some(tracked_message_id) = tracked_message_id {
+            if let Some(tracked_message_id) = self.get_tracked(incoming_message, position, TrackedKind::Text).await? {
                 self.api
                     .execute(EditMessageText::new(chat_id, tracked_message_id, text).parse_mode(ParseMode::Html))
                     .await?;
                 return Ok(());
             }
+            if self
+                .get_tracked(incoming_message, position, TrackedKind::Media)
+                .await?
+                .is_some()
+            {
+                return Ok(());
+            }
         }
-        self.send_new(&incoming_message, text, reply_to).await?;
+        self.send_new(incoming_message, position, text, reply_to).await?;
         Ok(())
     }
+
+    /// Send media in reply to a message
+    ///
+    /// Media can not replace an already sent message,
+    /// so nothing is sent when incoming message is edited after a reply.
+    ///
+    /// # Arguments
+    ///
+    /// * incoming_message - Message from update to track to
+    /// * position - Number of the reply to incoming message, see `send_nth()`
+    /// * media - Media to send
+    pub async fn send_media(
+        &self,
+        incoming_message: &Message,
+        position: usize,
+        media: Media,
+        reply_to: ReplyTo,
+    ) -> Result<(), SendError> {
+        if incoming_message.is_edited() {
+            for &kind in &[TrackedKind::Text, TrackedKind::Media] {
+                if self.get_tracked(incoming_message, position, kind).await?.is_some() {
+                    return Ok(());
+                }
+            }
+        }
+        let chat_id = incoming_message.get_chat_id();
+        let reply_to_id = get_reply_to_id(incoming_message, reply_to);
+        let result_message = match media {
+            Media::Animation { file_id, caption } => {
+                let mut method =
+                    SendAnimation::new(chat_id, InputFile::file_id(file_id)).reply_to_message_id(reply_to_id);
+                if let Some(caption) = caption {
+                    method = method.caption(caption).parse_mode(ParseMode::Html);
+                }
+                self.api.execute(method).await?
+            }
+            Media::Photo { file_id, caption } => {
+                let mut method = SendPhoto::new(chat_id, InputFile::file_id(file_id)).reply_to_message_id(reply_to_id);
+                if let Some(caption) = caption {
+                    method = method.caption(caption).parse_mode(ParseMode::Html);
+                }
+                self.api.execute(method).await?
+            }
+            Media::Sticker { file_id } => {
+                self.api
+                    .execute(SendSticker::new(chat_id, InputFile::file_id(file_id)).reply_to_message_id(reply_to_id))
+                    .await?
+            }
+        };
+        self.track(incoming_message, position, TrackedKind::Media, result_message.id)
+            .await
+    }
+}
+
+/// Kind of a message sent in reply to incoming message
+#[derive(Clone, Copy, Debug)]
+enum TrackedKind {
+    /// Text message, it can be edited
+    Text,
+    /// Sticker, animation or photo, it can not be edited to text
+    Media,
+}
+
+fn get_track_key(incoming_message: &Message, position: usize, kind: TrackedKind) -> String {
+    let mut key = match kind {
+        TrackedKind::Text => format!("{}{}", TRACK_MESSAGE_PREFIX, incoming_message.id),
+        TrackedKind::Media => format!("{}media:{}", TRACK_MESSAGE_PREFIX, incoming_message.id),
+    };
+    // The first reply keeps the key used before several replies were possible
+    if position > 0 {
+        key += &format!(":{}", position);
+    }
+    key
+}
+
+fn get_reply_to_id(incoming_message: &Message, reply_to: ReplyTo) -> Integer {
+    match reply_to {
+        ReplyTo::Incoming => incoming_message.id,
+        ReplyTo::Reply => match incoming_message.reply_to {
+            Some(ref reply_to) => reply_to.id,
+            None => incoming_message.id,
+        },
+    }
+}
+
+/// Media sent by file ID, captions are HTML
+#[derive(Clone, Debug, PartialEq)]
+pub enum Media {
+    Animation { file_id: String, caption: Option<String> },
+    Photo { file_id: String, caption: Option<String> },
+    Sticker { file_id: String },
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -0,0 +1,399 @@
+use super::json::JsonItem;
+use atom_syndication::Entry as AtomEntry;
+use carapax::types::ParseMode;
+use chrono::{DateTime, FixedOffset};
+use lazy_static::lazy_static;
+use regex::Regex;
+use rss::Item as RssItem;
+
+/// Template used when a feed has no template
+pub const DEFAULT_TEMPLATE: &str = r#"<a href="{link}">{title}</a>"#;
+
+const PUBLISHED_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Summaries are cut to this number of characters
+const MAX_SUMMARY_LENGTH: usize = 1000;
+
+/// Maximum length of a message in UTF-16 code units
+const MAX_MESSAGE_LENGTH: usize = 4096;
+
+/// Title is cut to this number of characters when an entry does not fit into a message,
+/// escaped title is at most 5 times longer
+const MAX_TITLE_LENGTH: usize = 800;
+
+/// Maximum length of an entity in characters including `&` and `;`
+const MAX_ENTITY_LENGTH: usize = 10;
+
+/// A feed entry to post
+#[derive(Debug, Default)]
+pub struct Entry {
+    /// Unique ID of the entry within a feed (guid, id or link)
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    pub author: Option<String>,
+    pub published: Option<DateTime<FixedOffset>>,
+    pub summary: Option<String>,
+}
+
+impl Entry {
+    /// Creates an entry from RSS item
+    ///
+    /// Returns None if item has no title or link
+    pub fn from_rss(item: &RssItem) -> Option<Self> {
+        match (item.title(), item.link()) {
+            (Some(title), Some(link)) => Some(Self {
+                id: String::from(item.guid().map(|x| x.value()).unwrap_or(link)),
+                title: String::from(title),
+                link: String::from(link),
+                author: item.author().map(String::from).or_else(|| {
+                    item.dublin_core_ext()
+                        .and_then(|x| x.creators().first())
+                        .map(String::from)
+                }),
+                published: item.pub_date().and_then(|x| DateTime::parse_from_rfc2822(x).ok()),
+                summary: item.description().map(String::from),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Creates an entry from Atom entry
+    ///
+    /// Returns None if entry has no links
+    pub fn from_atom(entry: &AtomEntry) -> Option<Self> {
+        entry.links().first().map(|link| Self {
+            id: String::from(if entry.id().is_empty() { link.href() } else { entry.id() }),
+            title: String::from(link.title().unwrap_or_else(|| entry.title())),
+            link: String::from(link.href()),
+            author: entry.authors().first().map(|x| String::from(x.name())),
+            published: Some(*entry.published().unwrap_or_else(|| entry.updated())),
+            summary: entry.summary().map(String::from),
+        })
+    }
+
+    /// Creates an entry from JSON Feed item
+    ///
+    /// Returns None if item has no URL, URL is used as title when item has no title
+    pub fn from_json(item: &JsonItem) -> Option<Self> {
+        item.url.as_ref().or(item.external_url.as_ref()).map(|link| Self {
+            id: item.id.clone(),
+            title: item.title.clone().unwrap_or_else(|| link.clone()),
+            link: link.clone(),
+            author: item
+                .authors
+                .iter()
+                .chain(item.author.iter())
+                .find_map(|x| x.name.clone()),
+            published: item
+                .date_published
+                .as_ref()
+                .or(item.date_modified.as_ref())
+                .and_then(|x| DateTime::parse_from_rfc3339(x).ok()),
+            summary: item
+                .summary
+                .clone()
+                .or_else(|| item.content_text.clone())
+                .or_else(|| item.content_html.clone()),
+        })
+    }
+
+    /// Renders entry using given template
+    ///
+    /// Supported placeholders:
+    ///
+    /// * `{title}` - Title of the entry
+    /// * `{link}` - Link to the entry
+    /// * `{author}` - Author of the entry
+    /// * `{published}` - Date when entry was published
+    /// * `{summary}` - Summary of the entry with HTML tags stripped and entities decoded,
+    ///   cut to 1000 characters
+    /// * `{feed_title}` - Title of the feed
+    ///
+    /// Unknown placeholders are kept as is.
+    pub fn render(&self, template: &str, feed_title: &str) -> String {
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let tail = &rest[start..];
+            let end = match tail.find('}') {
+                Some(end) => end,
+                None => break,
+            };
+            result.push_str(&rest[..start]);
+            let name = &tail[1..end];
+            match self.get_value(name, feed_title) {
+                Some(value) => result.push_str(&value),
+                None => result.push_str(&tail[..=end]),
+            }
+            rest = &tail[end + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+
+    /// Renders entry as a message using given template, see `render()`
+    ///
+    /// Default template is used when the message is too long for Telegram,
+    /// then a cut title is used alone.
+    pub fn render_message(&self, template: &str, feed_title: &str) -> String {
+        for &template in &[template, DEFAULT_TEMPLATE] {
+            let text = self.render(template, feed_title);
+            if text.encode_utf16().count() <= MAX_MESSAGE_LENGTH {
+                return text;
+            }
+        }
+        ParseMode::Html.escape(truncate(&self.title, MAX_TITLE_LENGTH))
+    }
+
+    fn get_value(&self, name: &str, feed_title: &str) -> Option<String> {
+        let escape = |x: &str| ParseMode::Html.escape(x);
+        Some(match name {
+            "title" => escape(&self.title),
+            // Link is usually placed into an attribute, so quotes are escaped too
+            "link" => escape(&self.link).replace('"', "&quot;"),
+            "author" => self.author.as_deref().map(escape).unwrap_or_default(),
+            "published" => self
+                .published
+                .map(|x| x.format(PUBLISHED_FORMAT).to_string())
+                .unwrap_or_default(),
+            "summary" => self
+                .summary
+                .as_deref()
+                .map(|x| escape(&truncate(decode_entities(&strip_tags(x)).trim(), MAX_SUMMARY_LENGTH)))
+                .unwrap_or_default(),
+            "feed_title" => escape(feed_title),
+            _ => return None,
+        })
+    }
+}
+
+/// Sorts entries from oldest to newest
+///
+/// Entries are sorted by publication date when all of them have it,
+/// otherwise feed order (newest first) is reversed.
+pub fn sort_chronologically(entries: &mut [Entry]) {
+    if entries.iter().all(|x| x.published.is_some()) {
+        entries.sort_by_key(|x| x.published);
+    } else {
+        entries.reverse();
+    }
+}
+
+lazy_static! {
+    // Regex is valid, so unwrap is safe here
+    static ref TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+}
+
+fn strip_tags(input: &str) -> String {
+    TAG.replace_all(input, "").into_owned()
+}
+
+/// Cuts text to given number of characters, `…` is added to a cut text
+fn truncate(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        String::from(text)
+    } else {
+        let mut result: String = text.chars().take(max_length - 1).collect();
+        result.push('…');
+        result
+    }
+}
+
+/// Decodes numeric and common named HTML entities, unknown entities are kept as is
+fn decode_entities(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        // Entity names are short, so a far semicolon does not end an entity
+        let decoded = rest
+            .char_indices()
+            .take(MAX_ENTITY_LENGTH)
+            .find(|&(_, c)| c == ';')
+            .and_then(|(end, _)| decode_entity(&rest[1..end]).map(|c| (c, end)));
+        match decoded {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Returns a character of an entity given without `&` and `;`
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(code) = name.strip_prefix('#') {
+        let code = match code.strip_prefix('x').or_else(|| code.strip_prefix('X')) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => code.parse().ok()?,
+        };
+        return std::char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "ndash" => '\u{2013}',
+        "mdash" => '\u{2014}',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201c}',
+        "rdquo" => '\u{201d}',
+        "laquo" => '\u{ab}',
+        "raquo" => '\u{bb}',
+        "hellip" => '\u{2026}',
+        "copy" => '\u{a9}',
+        "reg" => '\u{ae}',
+        "trade" => '\u{2122}',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> Entry {
+        Entry {
+            id: String::from("1"),
+            title: String::from("Rust <2020>"),
+            link: String::from("https://blog.rust-lang.org/2020"),
+            author: Some(String::from("Ferris")),
+            published: Some(DateTime::parse_from_rfc3339("2020-01-02T03:04:05+00:00").unwrap()),
+            summary: Some(String::from("<p>Call for <b>blogs</b></p>")),
+        }
+    }
+
+    fn dated(id: &str, published: Option<&str>) -> Entry {
+        Entry {
+            id: String::from(id),
+            published: published.map(|x| DateTime::parse_from_rfc3339(x).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn ids(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|x| x.id.as_str()).collect()
+    }
+
+    #[test]
+    fn sort_by_date() {
+        let mut entries = vec![
+            dated("b", Some("2020-01-02T00:00:00+00:00")),
+            dated("c", Some("2020-01-03T00:00:00+00:00")),
+            dated("a", Some("2020-01-01T00:00:00+00:00")),
+        ];
+        sort_chronologically(&mut entries);
+        assert_eq!(ids(&entries), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn sort_without_date() {
+        let mut entries = vec![
+            dated("c", Some("2020-01-03T00:00:00+00:00")),
+            dated("b", None),
+            dated("a", Some("2020-01-01T00:00:00+00:00")),
+        ];
+        sort_chronologically(&mut entries);
+        assert_eq!(ids(&entries), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn render_default() {
+        assert_eq!(
+            entry().render(DEFAULT_TEMPLATE, "Rust Blog"),
+            r#"<a href="https://blog.rust-lang.org/2020">Rust &lt;2020&gt;</a>"#
+        );
+    }
+
+    #[test]
+    fn render_placeholders() {
+        let template = "{feed_title}: {title} by {author} at {published}\n{summary}\n{link}";
+        assert_eq!(
+            entry().render(template, "Rust & Blog"),
+            "Rust &amp; Blog: Rust &lt;2020&gt; by Ferris at 2020-01-02 03:04\nCall for blogs\nhttps://blog.rust-lang.org/2020"
+        );
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(
+            decode_entities("Tom &amp; Jerry&#8217;s &#x1F980; &lt;b&gt; &mdash;&nbsp;ok"),
+            "Tom & Jerry\u{2019}s \u{1f980} <b> \u{2014}\u{a0}ok"
+        );
+        assert_eq!(
+            decode_entities("AT&T &unknown; &#xZZ; & x; &#1114112;"),
+            "AT&T &unknown; &#xZZ; & x; &#1114112;"
+        );
+        let entry = Entry {
+            summary: Some(String::from("<p>Rust &amp; Go &lt;3</p>")),
+            ..Default::default()
+        };
+        assert_eq!(entry.render("{summary}", ""), "Rust &amp; Go &lt;3");
+    }
+
+    #[test]
+    fn render_long() {
+        let entry = Entry {
+            title: String::from("Long"),
+            link: String::from("https://example.com"),
+            summary: Some("a".repeat(5000)),
+            ..Default::default()
+        };
+        let summary = entry.render("{summary}", "");
+        assert_eq!(summary.chars().count(), 1000);
+        assert!(summary.ends_with("a…"));
+        assert_eq!(entry.render_message("{title}", ""), "Long");
+
+        let template = format!("{}{{summary}}", "<b>x</b>".repeat(500));
+        assert_eq!(
+            entry.render_message(&template, ""),
+            r#"<a href="https://example.com">Long</a>"#
+        );
+
+        let entry = Entry {
+            title: "&".repeat(1000),
+            link: "x".repeat(5000),
+            ..Default::default()
+        };
+        let text = entry.render_message(DEFAULT_TEMPLATE, "");
+        assert_eq!(text, format!("{}…", "&amp;".repeat(799)));
+    }
+
+    #[test]
+    fn render_escaped_link() {
+        let entry = Entry {
+            title: String::from("Query"),
+            link: String::from(r#"https://example.com/?a=1&b=2"c"#),
+            ..Default::default()
+        };
+        assert_eq!(
+            entry.render(DEFAULT_TEMPLATE, "feed"),
+            r#"<a href="https://example.com/?a=1&amp;b=2&quot;c">Query</a>"#
+        );
+    }
+
+    #[test]
+    fn render_missing_values() {
+        let entry = Entry {
+            title: String::from("{link}"),
+            link: String::from("link"),
+            ..Default::default()
+        };
+        assert_eq!(
+            entry.render("{title} {author}{published}{summary} {unknown} {", "feed"),
+            "{link}  {unknown} {"
+        );
+    }
+}
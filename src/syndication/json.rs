@@ -0,0 +1,123 @@
+use serde::{Deserialize, Deserializer};
+use serde_json::Number;
+
+/// JSON Feed document
+///
+/// See https://jsonfeed.org/version/1.1 for more information
+#[derive(Debug, Deserialize)]
+pub struct JsonFeed {
+    pub title: String,
+    #[serde(default)]
+    pub items: Vec<JsonItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JsonItem {
+    #[serde(deserialize_with = "deserialize_id")]
+    pub id: String,
+    pub url: Option<String>,
+    pub external_url: Option<String>,
+    pub title: Option<String>,
+    pub content_html: Option<String>,
+    pub content_text: Option<String>,
+    pub summary: Option<String>,
+    pub date_published: Option<String>,
+    pub date_modified: Option<String>,
+    /// Authors of the item (version 1.1)
+    #[serde(default)]
+    pub authors: Vec<JsonAuthor>,
+    /// Author of the item (version 1.0)
+    pub author: Option<JsonAuthor>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JsonAuthor {
+    pub name: Option<String>,
+}
+
+/// Item ID is a string, but numbers are accepted too as the spec requires
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawId {
+    String(String),
+    Number(Number),
+}
+
+fn deserialize_id<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match RawId::deserialize(deserializer)? {
+        RawId::String(id) => id,
+        RawId::Number(id) => id.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syndication::entry::Entry;
+
+    #[test]
+    fn parse() {
+        let data = r#"{
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": "Rust Blog",
+            "items": [
+                {
+                    "id": "2",
+                    "url": "https://example.com/2",
+                    "title": "Second",
+                    "summary": "Second post",
+                    "date_published": "2020-01-02T00:00:00+00:00",
+                    "authors": [{"name": "Ferris"}]
+                },
+                {
+                    "id": "1",
+                    "url": "https://example.com/1",
+                    "content_text": "First post",
+                    "date_modified": "2020-01-01T00:00:00Z",
+                    "author": {"name": "Crab"}
+                },
+                {
+                    "id": "0",
+                    "content_text": "No link"
+                }
+            ]
+        }"#;
+        let feed: JsonFeed = serde_json::from_str(data).unwrap();
+        assert_eq!(feed.title, "Rust Blog");
+        let entries: Vec<Entry> = feed.items.iter().filter_map(Entry::from_json).collect();
+        assert_eq!(entries.len(), 2);
+
+        let entry = &entries[0];
+        assert_eq!(entry.id, "2");
+        assert_eq!(entry.title, "Second");
+        assert_eq!(entry.link, "https://example.com/2");
+        assert_eq!(entry.author.as_deref(), Some("Ferris"));
+        assert_eq!(entry.summary.as_deref(), Some("Second post"));
+        assert_eq!(entry.published.unwrap().to_rfc3339(), "2020-01-02T00:00:00+00:00");
+
+        let entry = &entries[1];
+        assert_eq!(entry.id, "1");
+        assert_eq!(entry.title, "https://example.com/1");
+        assert_eq!(entry.author.as_deref(), Some("Crab"));
+        assert_eq!(entry.summary.as_deref(), Some("First post"));
+        assert_eq!(entry.published.unwrap().to_rfc3339(), "2020-01-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn parse_numeric_id() {
+        let data = r#"{
+            "title": "Numbers",
+            "items": [
+                {"id": 123, "url": "https://example.com/123"},
+                {"id": "124", "url": "https://example.com/124"}
+            ]
+        }"#;
+        let feed: JsonFeed = serde_json::from_str(data).unwrap();
+        let ids: Vec<&str> = feed.items.iter().map(|x| x.id.as_str()).collect();
+        assert_eq!(ids, vec!["123", "124"]);
+        assert!(serde_json::from_str::<JsonFeed>(r#"{"title": "", "items": [{"id": null}]}"#).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use super::SyndicationError;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeedKind {
+    Atom,
+    /// Detect kind using response
+    Auto,
+    Json,
+    Rss,
+}
+
+impl FeedKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FeedKind::Atom => "atom",
+            FeedKind::Auto => "auto",
+            FeedKind::Json => "json",
+            FeedKind::Rss => "rss",
+        }
+    }
+
+    /// Detects kind of a feed
+    ///
+    /// Root element of a document is checked first,
+    /// `Content-Type` header is used when document is not recognized.
+    ///
+    /// # Arguments
+    ///
+    /// * content_type - Value of `Content-Type` header
+    /// * data - Response body
+    pub fn detect(content_type: Option<&str>, data: &[u8]) -> Option<Self> {
+        detect_by_root(data).or_else(|| content_type.and_then(detect_by_content_type))
+    }
+}
+
+impl FromStr for FeedKind {
+    type Err = SyndicationError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Ok(match raw {
+            "atom" => FeedKind::Atom,
+            "auto" => FeedKind::Auto,
+            "json" => FeedKind::Json,
+            "rss" => FeedKind::Rss,
+            _ => return Err(SyndicationError::UnknownFeedKind(String::from(raw))),
+        })
+    }
+}
+
+fn detect_by_content_type(content_type: &str) -> Option<FeedKind> {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    match mime.as_str() {
+        "application/atom+xml" => Some(FeedKind::Atom),
+        "application/feed+json" | "application/json" => Some(FeedKind::Json),
+        "application/rss+xml" | "application/rdf+xml" => Some(FeedKind::Rss),
+        _ => None,
+    }
+}
+
+fn detect_by_root(data: &[u8]) -> Option<FeedKind> {
+    let text = String::from_utf8_lossy(&data[..data.len().min(4096)]);
+    let mut rest = text.trim_start_matches('\u{feff}').trim_start();
+    if rest.starts_with('{') {
+        return Some(FeedKind::Json);
+    }
+    loop {
+        if let Some(tail) = rest.strip_prefix("<?") {
+            rest = &tail[tail.find("?>")? + 2..];
+        } else if let Some(tail) = rest.strip_prefix("<!--") {
+            rest = &tail[tail.find("-->")? + 3..];
+        } else if let Some(tail) = rest.strip_prefix("<!") {
+            rest = &tail[tail.find('>')? + 1..];
+        } else if let Some(tail) = rest.strip_prefix('<') {
+            let name = &tail[..tail.find(|c: char| c.is_whitespace() || c == '>' || c == '/')?];
+            let local_name = name.rsplit(':').next().unwrap_or(name);
+            return match local_name {
+                "feed" => Some(FeedKind::Atom),
+                "rss" | "RDF" => Some(FeedKind::Rss),
+                _ => None,
+            };
+        } else {
+            return None;
+        }
+        rest = rest.trim_start();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_by_root_element() {
+        let rss = br#"<?xml version="1.0"?><!-- comment --><rss version="2.0"><channel></channel></rss>"#;
+        assert_eq!(FeedKind::detect(None, rss), Some(FeedKind::Rss));
+
+        let rdf = br#"<?xml version="1.0"?>
+            <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"></rdf:RDF>"#;
+        assert_eq!(FeedKind::detect(None, rdf), Some(FeedKind::Rss));
+
+        let atom = "\u{feff}<?xml version=\"1.0\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\"></feed>";
+        assert_eq!(FeedKind::detect(None, atom.as_bytes()), Some(FeedKind::Atom));
+
+        let json = br#"  {"version": "https://jsonfeed.org/version/1.1"}"#;
+        assert_eq!(FeedKind::detect(None, json), Some(FeedKind::Json));
+    }
+
+    #[test]
+    fn root_element_takes_precedence() {
+        let atom = br#"<feed xmlns="http://www.w3.org/2005/Atom"></feed>"#;
+        assert_eq!(
+            FeedKind::detect(Some("application/rss+xml"), atom),
+            Some(FeedKind::Atom)
+        );
+    }
+
+    #[test]
+    fn detect_by_content_type() {
+        assert_eq!(
+            FeedKind::detect(Some("application/atom+xml; charset=utf-8"), b""),
+            Some(FeedKind::Atom)
+        );
+        assert_eq!(
+            FeedKind::detect(Some("application/feed+json"), b"garbage"),
+            Some(FeedKind::Json)
+        );
+        assert_eq!(FeedKind::detect(Some("text/html"), b"<html></html>"), None);
+        assert_eq!(FeedKind::detect(None, b""), None);
+    }
+}
@@ -0,0 +1,472 @@
+use self::{
+    entry::{sort_chronologically, Entry, DEFAULT_TEMPLATE},
+    json::JsonFeed,
+    kind::FeedKind,
+};
+use crate::context::Context;
+use atom_syndication::{Error as AtomError, Feed as AtomFeed};
+use bytes::buf::BufExt;
+use carapax::{
+    methods::SendMessage,
+    types::{Integer, ParseMode},
+    ExecuteError,
+};
+use reqwest::{
+    header::{HeaderMap, HeaderName, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Error as HttpError, StatusCode,
+};
+use rss::{Channel as RssChannel, Error as RssError};
+use serde_json::Error as JsonError;
+use std::{collections::HashSet, error::Error, fmt, time::Duration};
+use tokio::time::delay_for;
+use tokio_postgres::Error as PostgresError;
+
+mod entry;
+mod json;
+mod kind;
+
+/// Maximum number of entries posted per feed at once
+///
+/// Older unseen entries are marked as seen without posting.
+const MAX_ENTRIES_PER_POLL: usize = 5;
+
+/// Delay before the first retry of a failed feed, doubled after each failure
+const RETRY_BASE_DELAY: f64 = 60.0;
+
+/// Maximum delay between retries of a failed feed
+const RETRY_MAX_DELAY: f64 = 21_600.0;
+
+/// Number of consecutive failures after which a feed is disabled
+const MAX_FAILURES: i32 = 10;
+
+pub struct Syndication {
+    context: Context,
+}
+
+impl Syndication {
+    pub fn new(context: Context) -> Self {
+        Self { context }
+    }
+
+    async fn get_feeds(&self) -> Result<Vec<Feed>, SyndicationError> {
+        let mut result = Vec::new();
+        let rows = self
+            .context
+            .pg_client
+            .query(
+                "SELECT id, url, kind, initialized, chat_id, template, etag, last_modified
+                FROM feeds
+                WHERE enabled
+                AND (retry_at IS NULL OR retry_at <= now())
+                AND (extract(epoch from (now() - last_update)) >= timeout OR last_update IS NULL)",
+                &[],
+            )
+            .await
+            .map_err(SyndicationError::GetFeeds)?;
+        for row in rows {
+            let id: i32 = row.get(0);
+            let url: String = row.get(1);
+            let kind: String = row.get(2);
+            let initialized: bool = row.get(3);
+            let chat_id: Option<Integer> = row.get(4);
+            let template: Option<String> = row.get(5);
+            let etag: Option<String> = row.get(6);
+            let last_modified: Option<String> = row.get(7);
+            let kind = match kind.parse() {
+                Ok(kind) => kind,
+                Err(err) => {
+                    self.handle_failure(id, &url, err).await;
+                    continue;
+                }
+            };
+            result.push(Feed {
+                id,
+                url,
+                kind,
+                initialized,
+                chat_id,
+                template,
+                etag,
+                last_modified,
+            })
+        }
+        Ok(result)
+    }
+
+    /// Downloads and parses a feed
+    ///
+    /// Uses `ETag` and `Last-Modified` of the previous response to avoid
+    /// downloading a feed which has not been changed.
+    async fn get_content(&self, feed: &Feed) -> Result<FeedResponse, SyndicationError> {
+        let mut req = self.context.http_client.get(&feed.url);
+        if let Some(ref etag) = feed.etag {
+            req = req.header(IF_NONE_MATCH, etag.as_str());
+        }
+        if let Some(ref last_modified) = feed.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, last_modified.as_str());
+        }
+        let rep = req.send().await?;
+        let status = rep.status();
+        let headers = rep.headers();
+        let etag = get_header(headers, ETAG);
+        let last_modified = get_header(headers, LAST_MODIFIED);
+        let content_type = get_header(headers, CONTENT_TYPE);
+        if status == StatusCode::NOT_MODIFIED {
+            return Ok(FeedResponse {
+                etag,
+                last_modified,
+                detected_kind: None,
+                content: None,
+            });
+        }
+        if !status.is_success() {
+            return Err(SyndicationError::BadStatus(status));
+        }
+        let data = rep.bytes().await?;
+        let detected_kind = match feed.kind {
+            FeedKind::Auto => {
+                Some(FeedKind::detect(content_type.as_deref(), &data).ok_or(SyndicationError::UnknownFormat)?)
+            }
+            _ => None,
+        };
+        let content = match detected_kind.unwrap_or(feed.kind) {
+            FeedKind::Rss => {
+                let channel = RssChannel::read_from(data.reader())?;
+                FeedContent {
+                    title: String::from(channel.title()),
+                    entries: channel.items().iter().filter_map(Entry::from_rss).collect(),
+                }
+            }
+            FeedKind::Atom => {
+                let atom_feed = AtomFeed::read_from(data.reader())?;
+                FeedContent {
+                    title: String::from(atom_feed.title()),
+                    entries: atom_feed.entries().iter().filter_map(Entry::from_atom).collect(),
+                }
+            }
+            FeedKind::Json => {
+                let json_feed: JsonFeed = serde_json::from_slice(&data)?;
+                FeedContent {
+                    entries: json_feed.items.iter().filter_map(Entry::from_json).collect(),
+                    title: json_feed.title,
+                }
+            }
+            FeedKind::Auto => return Err(SyndicationError::UnknownFormat),
+        };
+        Ok(FeedResponse {
+            etag,
+            last_modified,
+            detected_kind,
+            content: Some(content),
+        })
+    }
+
+    async fn get_seen_entries(&self, feed_id: i32, entry_ids: &[String]) -> Result<HashSet<String>, SyndicationError> {
+        Ok(self
+            .context
+            .pg_client
+            .query(
+                "SELECT entry_id FROM feed_entries WHERE feed_id = $1 AND entry_id = ANY($2)",
+                &[&feed_id, &entry_ids],
+            )
+            .await
+            .map_err(SyndicationError::GetEntries)?
+            .into_iter()
+            .map(|row| row.get(0))
+            .collect())
+    }
+
+    async fn save_seen_entries(&self, feed_id: i32, entry_ids: &[String]) -> Result<(), SyndicationError> {
+        self.context
+            .pg_client
+            .execute(
+                "INSERT INTO feed_entries (feed_id, entry_id)
+                SELECT $1, unnest($2::text[])
+                ON CONFLICT DO NOTHING",
+                &[&feed_id, &entry_ids],
+            )
+            .await
+            .map_err(SyndicationError::SaveEntries)?;
+        Ok(())
+    }
+
+    /// Removes old entries which are not present in a feed anymore
+    async fn prune_seen_entries(&self, feed_id: i32, entry_ids: &[String]) -> Result<(), SyndicationError> {
+        self.context
+            .pg_client
+            .execute(
+                "DELETE FROM feed_entries
+                WHERE feed_id = $1
+                AND created_at < now() - interval '30 days'
+                AND NOT (entry_id = ANY($2))",
+                &[&feed_id, &entry_ids],
+            )
+            .await
+            .map_err(SyndicationError::SaveEntries)?;
+        Ok(())
+    }
+
+    /// Sends an entry to every chat of a feed
+    ///
+    /// Succeeds when the entry is sent to at least one chat, so it is not reposted
+    /// to other chats on next poll, failures of other chats are logged.
+    async fn send_entry(&self, feed: &Feed, feed_title: &str, entry: &Entry) -> Result<(), SyndicationError> {
+        let template = feed.template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+        let text = entry.render_message(template, feed_title);
+        let chat_ids = match feed.chat_id {
+            Some(chat_id) => vec![chat_id],
+            None => self.context.config.chat_ids.clone(),
+        };
+        let mut sent = false;
+        let mut last_error = None;
+        for chat_id in chat_ids {
+            match self
+                .context
+                .api
+                .execute(SendMessage::new(chat_id, text.as_str()).parse_mode(ParseMode::Html))
+                .await
+            {
+                Ok(_) => sent = true,
+                Err(err) => {
+                    log::error!(
+                        "failed to send entry {} of feed {} to chat {}: {}",
+                        entry.id,
+                        feed.url,
+                        chat_id,
+                        err
+                    );
+                    last_error = Some(err);
+                }
+            }
+        }
+        match last_error {
+            Some(err) if !sent => Err(SyndicationError::SendMessage(err)),
+            _ => Ok(()),
+        }
+    }
+
+    async fn process_feed(&self, feed: &Feed) -> Result<(), SyndicationError> {
+        let mut rep = self.get_content(feed).await?;
+        let content = match rep.content.take() {
+            Some(content) => content,
+            None => return self.mark_updated(feed.id, &rep).await,
+        };
+        let entry_ids: Vec<String> = content.entries.iter().map(|x| x.id.clone()).collect();
+        let seen = self.get_seen_entries(feed.id, &entry_ids).await?;
+        let mut known = HashSet::new();
+        let mut entries: Vec<Entry> = content
+            .entries
+            .into_iter()
+            .filter(|x| !seen.contains(&x.id) && known.insert(x.id.clone()))
+            .collect();
+        if feed.initialized {
+            sort_chronologically(&mut entries);
+            let skip = entries.len().saturating_sub(MAX_ENTRIES_PER_POLL);
+            if skip > 0 {
+                log::warn!(
+                    "feed {} has {} unseen entries, skipping {}",
+                    feed.url,
+                    entries.len(),
+                    skip
+                );
+                let skipped: Vec<String> = entries.drain(..skip).map(|x| x.id).collect();
+                self.save_seen_entries(feed.id, &skipped).await?;
+            }
+            for entry in entries {
+                self.send_entry(feed, &content.title, &entry).await?;
+                self.save_seen_entries(feed.id, &[entry.id]).await?;
+            }
+        } else {
+            // Do not post existing entries of a new feed
+            let unseen: Vec<String> = entries.into_iter().map(|x| x.id).collect();
+            self.save_seen_entries(feed.id, &unseen).await?;
+        }
+        self.prune_seen_entries(feed.id, &entry_ids).await?;
+        self.mark_updated(feed.id, &rep).await
+    }
+
+    async fn mark_updated(&self, feed_id: i32, rep: &FeedResponse) -> Result<(), SyndicationError> {
+        self.context
+            .pg_client
+            .execute(
+                "UPDATE feeds
+                SET last_update = now(),
+                    initialized = true,
+                    failures = 0,
+                    last_error = NULL,
+                    retry_at = NULL,
+                    etag = COALESCE($2, etag),
+                    last_modified = COALESCE($3, last_modified),
+                    kind = COALESCE($4, kind)
+                WHERE id = $1",
+                &[
+                    &feed_id,
+                    &rep.etag,
+                    &rep.last_modified,
+                    &rep.detected_kind.map(FeedKind::as_str),
+                ],
+            )
+            .await
+            .map_err(SyndicationError::UpdateFeed)?;
+        Ok(())
+    }
+
+    /// Saves an error and postpones next update of a feed
+    ///
+    /// Feed is disabled after `MAX_FAILURES` consecutive failures.
+    async fn record_failure(&self, feed_id: i32, err: &SyndicationError) -> Result<bool, SyndicationError> {
+        let row = self
+            .context
+            .pg_client
+            .query_one(
+                "UPDATE feeds
+                SET failures = failures + 1,
+                    last_error = $2,
+                    last_update = now(),
+                    retry_at = now() + make_interval(secs => LEAST($3 * power(2, failures), $4)),
+                    enabled = failures + 1 < $5
+                WHERE id = $1
+                RETURNING enabled",
+                &[
+                    &feed_id,
+                    &err.to_string(),
+                    &RETRY_BASE_DELAY,
+                    &RETRY_MAX_DELAY,
+                    &MAX_FAILURES,
+                ],
+            )
+            .await
+            .map_err(SyndicationError::UpdateFeed)?;
+        Ok(row.get(0))
+    }
+
+    async fn handle_failure(&self, feed_id: i32, url: &str, err: SyndicationError) {
+        log::error!("failed to process feed {}: {}", url, err);
+        match self.record_failure(feed_id, &err).await {
+            Ok(true) => {}
+            Ok(false) => log::warn!("feed {} is disabled after {} failures", url, MAX_FAILURES),
+            Err(err) => log::error!("{}", err),
+        }
+    }
+
+    pub async fn run(self) {
+        let timeout = Duration::from_secs(60);
+        loop {
+            match self.get_feeds().await {
+                Ok(feeds) => {
+                    for feed in feeds {
+                        if let Err(err) = self.process_feed(&feed).await {
+                            self.handle_failure(feed.id, &feed.url, err).await;
+                        }
+                    }
+                }
+                Err(err) => log::error!("{}", err),
+            }
+            delay_for(timeout).await
+        }
+    }
+}
+
+struct Feed {
+    id: i32,
+    url: String,
+    kind: FeedKind,
+    initialized: bool,
+    chat_id: Option<Integer>,
+    template: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+struct FeedResponse {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Kind detected for a feed with `auto` kind
+    detected_kind: Option<FeedKind>,
+    /// None when feed has not been modified since previous request
+    content: Option<FeedContent>,
+}
+
+struct FeedContent {
+    title: String,
+    entries: Vec<Entry>,
+}
+
+fn get_header(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers.get(name).and_then(|x| x.to_str().ok()).map(String::from)
+}
+
+#[derive(Debug)]
+pub enum SyndicationError {
+    Atom(AtomError),
+    BadStatus(StatusCode),
+    GetEntries(PostgresError),
+    GetFeeds(PostgresError),
+    HttpRequest(HttpError),
+    Json(JsonError),
+    Rss(RssError),
+    SaveEntries(PostgresError),
+    SendMessage(ExecuteError),
+    UpdateFeed(PostgresError),
+    UnknownFeedKind(String),
+    UnknownFormat,
+}
+
+impl From<AtomError> for SyndicationError {
+    fn from(err: AtomError) -> Self {
+        SyndicationError::Atom(err)
+    }
+}
+
+impl From<HttpError> for SyndicationError {
+    fn from(err: HttpError) -> Self {
+        SyndicationError::HttpRequest(err)
+    }
+}
+
+impl From<JsonError> for SyndicationError {
+    fn from(err: JsonError) -> Self {
+        SyndicationError::Json(err)
+    }
+}
+
+impl From<RssError> for SyndicationError {
+    fn from(err: RssError) -> Self {
+        SyndicationError::Rss(err)
+    }
+}
+
+impl Error for SyndicationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SyndicationError::GetEntries(err) => Some(err),
+            SyndicationError::GetFeeds(err) => Some(err),
+            SyndicationError::HttpRequest(err) => Some(err),
+            SyndicationError::Json(err) => Some(err),
+            SyndicationError::Rss(err) => Some(err),
+            SyndicationError::SaveEntries(err) => Some(err),
+            SyndicationError::SendMessage(err) => Some(err),
+            SyndicationError::UpdateFeed(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SyndicationError {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyndicationError::Atom(err) => write!(out, "failed to parse atom feed: {}", err),
+            SyndicationError::BadStatus(status) => write!(out, "server repsond with {} status code", status),
+            SyndicationError::GetEntries(err) => write!(out, "failed to get feed entries: {}", err),
+            SyndicationError::GetFeeds(err) => write!(out, "failed to get feeds: {}", err),
+            SyndicationError::HttpRequest(err) => write!(out, "http request error: {}", err),
+            SyndicationError::Json(err) => write!(out, "failed to parse JSON feed: {}", err),
+            SyndicationError::Rss(err) => write!(out, "failed to parse RSS: {}", err),
+            SyndicationError::SaveEntries(err) => write!(out, "failed to save feed entries: {}", err),
+            SyndicationError::SendMessage(err) => write!(out, "failed to send message: {}", err),
+            SyndicationError::UpdateFeed(err) => write!(out, "failed to update feed: {}", err),
+            SyndicationError::UnknownFeedKind(kind) => write!(out, "unknown feed kind: {}", kind),
+            SyndicationError::UnknownFormat => write!(out, "can not detect feed kind"),
+        }
+    }
+}